@@ -1,7 +1,21 @@
-{ ... }:
+{
+  seed ? 0,
+  propertyIterations ? 100,
+  only ? null,
+  listOnly ? false,
+  traceTests ? false,
+  ...
+}:
 let
   concatMap = f: list: builtins.concatLists (map f list);
   all = pred: list: builtins.all pred list;
+  mod = a: b: a - b * (a / b);
+  range = n: builtins.genList (i: i) n;
+  dedupe = list: builtins.foldl' (acc: x: if builtins.elem x acc then acc else acc ++ [ x ]) [ ] list;
+  isPrefix =
+    prefix: list:
+    builtins.length prefix <= builtins.length list
+    && builtins.genList (builtins.elemAt list) (builtins.length prefix) == prefix;
 
   checkEq =
     expected: actual:
@@ -26,6 +40,450 @@ let
     else
       true;
 
+  # Park-Miller "minimal standard" generator: small enough to never overflow Nix integers.
+  random = rec {
+    modulus = 2147483647;
+    init = s: mod (if s < 0 then -s else s) (modulus - 1) + 1;
+    next = state: mod (state * 48271) modulus;
+    between =
+      lo: hi: state:
+      let
+        nextState = next state;
+      in
+      {
+        value = lo + mod nextState (hi - lo + 1);
+        state = nextState;
+      };
+  };
+
+  generateMany =
+    generator: count: state: size:
+    builtins.foldl' (
+      acc: _:
+      let
+        generated = generator.generate acc.state size;
+      in
+      {
+        values = acc.values ++ [ generated.value ];
+        inherit (generated) state;
+      }
+    ) {
+      values = [ ];
+      inherit state;
+    } (range count);
+
+  removeAt =
+    index: list:
+    builtins.genList (i: builtins.elemAt list (if i < index then i else i + 1)) (
+      builtins.length list - 1
+    );
+
+  replaceAt =
+    index: value: list:
+    builtins.genList (i: if i == index then value else builtins.elemAt list i) (builtins.length list);
+
+  shrinkTowards =
+    target: n:
+    builtins.filter (c: c != n) (dedupe [
+      target
+      (target + (n - target) / 2)
+      (if n > target then n - 1 else n + 1)
+    ]);
+
+  alphabet = "abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789 ";
+
+  charFrom = chars: {
+    generate =
+      state: _:
+      let
+        index = random.between 0 (builtins.stringLength chars - 1) state;
+      in
+      {
+        value = builtins.substring index.value 1 chars;
+        inherit (index) state;
+      };
+    shrink = _: [ ];
+  };
+
+  stringsFrom = chars: minLength: maxLength: {
+    generate =
+      state: size:
+      let
+        length = random.between minLength (if maxLength == null then size else maxLength) state;
+        generated = generateMany (charFrom chars) length.value length.state size;
+      in
+      {
+        value = builtins.concatStringsSep "" generated.values;
+        inherit (generated) state;
+      };
+    shrink =
+      s:
+      let
+        length = builtins.stringLength s;
+        candidates = [
+          (builtins.substring 0 minLength s)
+          (builtins.substring 0 (length / 2) s)
+        ]
+        ++ builtins.genList (i: builtins.substring 0 i s + builtins.substring (i + 1) length s) length;
+      in
+      builtins.filter (c: c != s && builtins.stringLength c >= minLength) (dedupe candidates);
+  };
+
+  generators = {
+    ints = {
+      generate = state: size: random.between (-size) size state;
+      shrink = shrinkTowards 0;
+    };
+
+    intsBetween = lo: hi: {
+      generate = state: _: random.between lo hi state;
+      shrink = shrinkTowards lo;
+    };
+
+    bools = {
+      generate =
+        state: _:
+        let
+          bit = random.between 0 1 state;
+        in
+        {
+          value = bit.value == 1;
+          inherit (bit) state;
+        };
+      shrink = b: if b then [ false ] else [ ];
+    };
+
+    elements =
+      values:
+      if values == [ ] then
+        throw "gen.elements: expected a non-empty list of values"
+      else
+        {
+          generate =
+            state: _:
+            let
+              index = random.between 0 (builtins.length values - 1) state;
+            in
+            {
+              value = builtins.elemAt values index.value;
+              inherit (index) state;
+            };
+          shrink = _: [ ];
+        };
+
+    strings = stringsFrom alphabet 0 null;
+
+    listsOf = generator: {
+      generate =
+        state: size:
+        let
+          length = random.between 0 size state;
+          generated = generateMany generator length.value length.state size;
+        in
+        {
+          value = generated.values;
+          inherit (generated) state;
+        };
+      shrink =
+        list:
+        let
+          length = builtins.length list;
+          candidates = [
+            [ ]
+            (builtins.genList (builtins.elemAt list) (length / 2))
+          ]
+          ++ builtins.genList (i: removeAt i list) length
+          ++ concatMap (
+            i: map (x: replaceAt i x list) (generator.shrink (builtins.elemAt list i))
+          ) (range length);
+        in
+        builtins.filter (c: c != list) (dedupe candidates);
+    };
+
+    attrsOf = generator: {
+      generate =
+        state: size:
+        let
+          length = random.between 0 size state;
+          names = generateMany (stringsFrom "abcdefghijklmnopqrstuvwxyz" 1 8) length.value length.state size;
+          values = generateMany generator length.value names.state size;
+        in
+        {
+          value = builtins.listToAttrs (
+            builtins.genList (i: {
+              name = builtins.elemAt names.values i;
+              value = builtins.elemAt values.values i;
+            }) length.value
+          );
+          inherit (values) state;
+        };
+      shrink =
+        attrs:
+        let
+          names = builtins.attrNames attrs;
+        in
+        map (name: builtins.removeAttrs attrs [ name ]) names
+        ++ concatMap (
+          name: map (value: attrs // { ${name} = value; }) (generator.shrink attrs.${name})
+        ) names;
+    };
+  };
+
+  maxShrinks = 100;
+
+  propertyFailure =
+    result:
+    if result == true then
+      null
+    else if result == false then
+      "Property does not hold"
+    else if builtins.isString result then
+      result
+    else
+      throw "Property must return either boolean or string, got: ${builtins.typeOf result}";
+
+  shrinkInput =
+    generator: predicate: shrinks: input: failure:
+    let
+      candidates = map (
+        candidate:
+        let
+          evaluated = builtins.tryEval (propertyFailure (predicate candidate));
+        in
+        {
+          input = candidate;
+          failure = if evaluated.success then evaluated.value else null;
+        }
+      ) (generator.shrink input);
+      failing = builtins.filter (c: c.failure != null) candidates;
+    in
+    if shrinks >= maxShrinks || failing == [ ] then
+      { inherit input failure shrinks; }
+    else
+      let
+        next = builtins.head failing;
+      in
+      shrinkInput generator predicate (shrinks + 1) next.input next.failure;
+
+  runProperty =
+    generator: predicate: _:
+    let
+      run = builtins.foldl' (
+        acc: iteration:
+        if acc ? failure then
+          acc
+        else
+          let
+            generated = generator.generate acc.state (mod iteration 100);
+            failure = propertyFailure (predicate generated.value);
+          in
+          if failure == null then
+            { inherit (generated) state; }
+          else
+            {
+              inherit iteration failure;
+              input = generated.value;
+            }
+      ) { state = random.init seed; } (range propertyIterations);
+      shrunk = shrinkInput generator predicate 0 run.input run.failure;
+    in
+    if run ? failure then
+      {
+        _failure = shrunk.failure;
+        _property = {
+          inherit seed;
+          inherit (shrunk) input shrinks;
+          iterations = run.iteration + 1;
+        };
+      }
+    else
+      true;
+
+  hasInfix =
+    infix: s:
+    let
+      infixLength = builtins.stringLength infix;
+      length = builtins.stringLength s;
+    in
+    length >= infixLength
+    && builtins.any (i: builtins.substring i infixLength s == infix) (
+      range (length - infixLength + 1)
+    );
+
+  splitPath =
+    path: if builtins.isList path then path else builtins.filter builtins.isString (builtins.split "\\." path);
+  showPath = path: if builtins.isList path then builtins.concatStringsSep "." path else path;
+
+  attrByPath =
+    path: attrs:
+    builtins.foldl'
+      (
+        acc: name:
+        if acc.found && builtins.isAttrs acc.value && acc.value ? ${name} then
+          {
+            found = true;
+            value = acc.value.${name};
+          }
+        else
+          {
+            found = false;
+            value = null;
+          }
+      )
+      {
+        found = true;
+        value = attrs;
+      }
+      path;
+
+  isOption = value: builtins.isAttrs value && (value._type or null) == "option";
+
+  evalModule =
+    {
+      modules,
+      specialArgs ? { },
+      lib ? import <nixpkgs/lib>,
+    }:
+    lib.evalModules { inherit modules specialArgs; };
+
+  findOption =
+    path: evaluated:
+    let
+      option = attrByPath (splitPath path) evaluated.options;
+    in
+    if option.found && isOption option.value then option.value else null;
+
+  checkHasOption =
+    path: evaluated:
+    if findOption path evaluated != null then
+      true
+    else
+      "Expected: option '${showPath path}' to exist\nGot: missing";
+
+  checkOptionEquals =
+    path: expected: evaluated:
+    let
+      option = findOption path evaluated;
+      result = checkEq expected option.value;
+    in
+    if option == null then
+      "Expected: option '${showPath path}' to exist\nGot: missing"
+    else if result == true then
+      true
+    else
+      {
+        _failure = result;
+        _definitions = map (definition: toString definition.file) (
+          option.definitionsWithLocations or [ ]
+        );
+      };
+
+  failedAssertions =
+    evaluated:
+    map (a: a.message) (builtins.filter (a: !a.assertion) (evaluated.config.assertions or [ ]));
+
+  checkAssertionsPass =
+    evaluated:
+    let
+      failed = failedAssertions evaluated;
+    in
+    if failed == [ ] then
+      true
+    else
+      "Expected: all assertions to pass\nGot: ${builtins.toJSON failed}";
+
+  checkAssertionsFail =
+    message: evaluated:
+    let
+      failed = failedAssertions evaluated;
+    in
+    if builtins.any (hasInfix message) failed then
+      true
+    else
+      "Expected: a failed assertion containing '${message}'\nGot: ${builtins.toJSON failed}";
+
+  checkWarningsContain =
+    message: evaluated:
+    let
+      warnings = evaluated.config.warnings or [ ];
+    in
+    if builtins.any (hasInfix message) warnings then
+      true
+    else
+      "Expected: a warning containing '${message}'\nGot: ${builtins.toJSON warnings}";
+
+  hasStringPrefix =
+    prefix: s: builtins.substring 0 (builtins.stringLength prefix) s == prefix;
+
+  # Files from the store or a lookup path such as `<nixpkgs>` are imported
+  # as usual, which keeps them cached between imports
+  isExternal =
+    key:
+    builtins.any (root: root != "" && hasStringPrefix root key) (
+      [ builtins.storeDir ] ++ map (entry: entry.path) builtins.nixPath
+    );
+
+  mockNames =
+    overrides:
+    concatMap (
+      kind: map (name: "${kind}.${name}") (builtins.attrNames (overrides.${kind} or { }))
+    ) [ "env" "builtins" "imports" ];
+
+  mockScope =
+    overrides:
+    let
+      env = overrides.env or { };
+      imports = overrides.imports or { };
+
+      mockedImport =
+        path:
+        let
+          key = toString path;
+          replacement = imports.${key};
+        in
+        if imports ? ${key} then
+          if builtins.isPath replacement || builtins.isString replacement then
+            scopedImport scope replacement
+          else
+            replacement
+        else if isExternal key then
+          builtins.import path
+        else
+          scopedImport scope path;
+
+      mockedBuiltins =
+        builtins
+        // (overrides.builtins or { })
+        // {
+          getEnv = name: env.${name} or (builtins.getEnv name);
+          import = mockedImport;
+          scopedImport = extra: scopedImport (scope // extra);
+        };
+
+      scope = {
+        builtins = mockedBuiltins;
+        import = mockedImport;
+      };
+    in
+    scope;
+
+  # The runner collects the traced names to report the active mocks
+  mockImport =
+    overrides: path:
+    let
+      imported = scopedImport (mockScope overrides) path;
+    in
+    builtins.foldl' (value: name: builtins.trace "nix-tests mock: ${name}" value) imported (
+      mockNames overrides
+    );
+
+  buildCheck = outputContains: drv: {
+    _build = {
+      drv_path = drv.drvPath;
+      output_contains = outputContains;
+    };
+  };
+
   helpers =
     let
       check = checkFn: actual: {
@@ -34,7 +492,17 @@ let
       };
     in
     {
-      inherit check;
+      inherit check forEach;
+      gen = generators;
+      forAll = generator: predicate: check (runProperty generator predicate) null;
+      builds = drv: check (buildCheck null) drv;
+      buildOutputContains = drv: path: check (buildCheck path) drv;
+      inherit evalModule mockImport;
+      hasOption = path: evaluated: check (checkHasOption path) evaluated;
+      optionEquals = path: expected: evaluated: check (checkOptionEquals path expected) evaluated;
+      assertionsPass = evaluated: check checkAssertionsPass evaluated;
+      assertionsFail = message: evaluated: check (checkAssertionsFail message) evaluated;
+      warningsContain = message: evaluated: check (checkWarningsContain message) evaluated;
       isEq = actual: expected: check (checkEq expected) actual;
       isTrue = actual: check (checkEq true) actual;
       isFalse = actual: check (checkEq false) actual;
@@ -44,6 +512,11 @@ let
       hasNotAttr = attrName: attrSet: check (checkHasNotAttr attrName) attrSet;
     };
 
+  forEach = cases: spec: {
+    _cases = cases;
+    _spec = spec;
+  };
+
   getLocation = pos: if pos != null then "${pos.file}:${toString pos.line}" else "unknown";
 
   sortByLine =
@@ -69,14 +542,25 @@ let
       checkDef = checkDefs.${name};
       checkResult = checkDef._checkFn checkDef._actual;
 
+      isFailure = builtins.isAttrs checkResult && checkResult ? _failure;
+      isBuild = builtins.isAttrs checkResult && checkResult ? _build;
+
       success =
         if builtins.isBool checkResult then
           checkResult
-        else if builtins.isString checkResult then
+        else if builtins.isString checkResult || isFailure then
           false
+        else if isBuild then
+          true
         else
           throw "Check must return either boolean or string, got: ${builtins.typeOf checkResult}";
-      failure = if builtins.isString checkResult then checkResult else null;
+      failure =
+        if builtins.isString checkResult then
+          checkResult
+        else if isFailure then
+          checkResult._failure
+        else
+          null;
     in
     {
       inherit
@@ -85,27 +569,101 @@ let
         success
         failure
         ;
-    };
+    }
+    // (if isFailure && checkResult ? _property then { property = checkResult._property; } else { })
+    // (
+      if isFailure && checkResult ? _definitions then
+        { definitions = checkResult._definitions; }
+      else
+        { }
+    )
+    // (if isBuild then { build = checkResult._build; } else { });
 
-  runTest =
-    path: location: spec:
+  caseLabel =
+    index: value:
+    if builtins.isString value then
+      value
+    else if builtins.isInt value || builtins.isFloat value || builtins.isBool value then
+      builtins.toJSON value
+    else
+      "case ${toString index}";
+
+  expandCases =
+    location: cases:
+    if builtins.isList cases then
+      let
+        labels = builtins.genList (index: caseLabel index (builtins.elemAt cases index)) (
+          builtins.length cases
+        );
+        count = label: builtins.length (builtins.filter (other: other == label) labels);
+      in
+      builtins.genList (
+        index:
+        let
+          label = builtins.elemAt labels index;
+        in
+        {
+          # Repeated values get their index, so every case has its own test path
+          label = if count label > 1 then "${label} #${toString index}" else label;
+          value = builtins.elemAt cases index;
+          inherit location;
+        }
+      ) (builtins.length cases)
+    else
+      map (
+        name:
+        let
+          pos = builtins.unsafeGetAttrPos name cases;
+        in
+        {
+          label = name;
+          value = cases.${name};
+          location = if pos != null then getLocation pos else location;
+        }
+      ) (sortByLine cases);
+
+  runChecks =
+    path: location: testResult:
     let
-      testResult = spec helpers;
       checkNames = builtins.filter (name: isCheck testResult.${name}) (sortByLine testResult);
       checks = map (runCheck testResult) checkNames;
       success = all (c: c.success) checks;
     in
-    {
-      inherit
-        path
-        location
-        success
-        checks
-        ;
-    };
+    # With `listOnly` set, checks are only named, not run. `isCheck` only
+    # looks at the attributes of each check, never at the values compared
+    if listOnly then
+      {
+        inherit path location;
+        checks = map (name: {
+          inherit name;
+          location = getLocation (builtins.unsafeGetAttrPos name testResult);
+        }) checkNames;
+      }
+    else
+      {
+        inherit
+          path
+          location
+          success
+          checks
+          ;
+      };
+
+  runTest =
+    path: location: spec:
+    let
+      testResult = spec helpers;
+    in
+    if isCases testResult then
+      map (
+        c: runChecks (path ++ [ c.label ]) c.location (testResult._spec c.value) // { case = c.label; }
+      ) (expandCases location testResult._cases)
+    else
+      [ (runChecks path location testResult) ];
 
   isTest = value: builtins.isFunction value;
   isCheck = value: builtins.isAttrs value && value ? _checkFn && value ? _actual;
+  isCases = value: builtins.isAttrs value && value ? _cases && value ? _spec;
 
   flattenTests =
     pathPrefix: attrs:
@@ -116,15 +674,30 @@ let
         newPath = pathPrefix ++ [ name ];
         location = getLocation (builtins.unsafeGetAttrPos name attrs);
       in
-      if isTest value then [ (runTest newPath location value) ] else flattenTests newPath value
+      # With `only` set, just the tests at that path are run: the test itself,
+      # or every test of the group
+      if only != null && !(isPrefix newPath only || isPrefix only newPath) then
+        [ ]
+      else if isTest value then
+        runTest newPath location value
+      else
+        flattenTests newPath value
     ) (sortByLine attrs);
+
+  # With `traceTests` set, the report of each test is traced as soon as it's
+  # run, so the runner can show results before the whole file is evaluated
+  traceTest =
+    report:
+    if traceTests then builtins.trace "nix-tests test: ${builtins.toJSON report}" report else report;
 in
 {
+  inherit mockImport mockScope;
+
   runTests =
     tests:
     let
       result = {
-        tests = flattenTests [ ] tests;
+        tests = map traceTest (flattenTests [ ] tests);
       };
     in
     builtins.deepSeq result result;
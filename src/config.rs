@@ -1,5 +1,5 @@
 use std::{
-    env::current_dir,
+    env::{self, current_dir},
     path::{Path, PathBuf},
 };
 
@@ -19,6 +19,14 @@ pub struct Config {
     pub report: report_config::Config,
 }
 
+pub fn cache_dir() -> PathBuf {
+    env::var_os("XDG_CACHE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))
+        .unwrap_or_else(env::temp_dir)
+        .join("nix-tests")
+}
+
 impl TryFrom<PathBuf> for Config {
     type Error = anyhow::Error;
 
@@ -45,22 +53,47 @@ impl Config {
     }
 
     pub fn search_in(dir: &Path) -> anyhow::Result<Option<Self>> {
+        Self::find_in(dir).map(Config::try_from).transpose()
+    }
+
+    /// Returns the path of the config file `search_in` loads.
+    pub fn find_in(dir: &Path) -> Option<PathBuf> {
         let config_path = dir.join(".nix-tests.toml");
 
         if config_path.exists() {
-            let config = Config::try_from(config_path.to_path_buf())?;
-            return Ok(Some(config));
+            return Some(config_path);
         }
 
         if dir.join("flake.lock").exists() || dir.join(".git").exists() {
-            return Ok(None);
+            return None;
         }
 
-        if let Some(parent) = dir.parent() {
-            Self::search_in(parent)
-        } else {
-            Ok(None)
+        Self::find_in(dir.parent()?)
+    }
+
+    /// Returns the root of the project `dir` is in: the closest directory with
+    /// a config file, `flake.lock` or `.git`.
+    pub fn find_root(dir: &Path) -> Option<PathBuf> {
+        if [".nix-tests.toml", "flake.lock", ".git"]
+            .iter()
+            .any(|name| dir.join(name).exists())
+        {
+            return Some(dir.to_path_buf());
         }
+
+        Self::find_root(dir.parent()?)
+    }
+
+    /// Returns the keys of a config that aren't known options, which parsing
+    /// otherwise silently ignores.
+    pub fn unknown_keys(content: &str) -> anyhow::Result<Vec<String>> {
+        let mut unknown = Vec::new();
+        let deserializer = toml::Deserializer::parse(content).context("Failed to parse config")?;
+        let _: Config =
+            serde_ignored::deserialize(deserializer, |path| unknown.push(path.to_string()))
+                .context("Failed to parse config")?;
+
+        Ok(unknown)
     }
 }
 
@@ -79,6 +112,49 @@ mod config_parsing_tests {
         let config = super::Config::try_from(toml_str.to_string()).unwrap();
         check!(config.runner.num_threads == NumThreads::new(8));
     }
+
+    #[test]
+    fn it_finds_unknown_keys() {
+        let toml_str = r#"
+            verbose = true
+
+            [runner]
+            num-threads = 8
+            num-thread = 4
+
+            [report]
+            format = "json"
+            hide-passed = true
+        "#;
+        check!(
+            super::Config::unknown_keys(toml_str).unwrap()
+                == vec!["report.hide-passed", "runner.num-thread", "verbose"]
+        );
+        check!(super::Config::unknown_keys("[runner]\nnum-threads = \"many\"").is_err());
+    }
+
+    #[test]
+    fn it_parses_runner_overrides() {
+        let toml_str = r#"
+            [runner.overrides.env]
+            HOME = "/home/test"
+
+            [runner.overrides.builtins]
+            currentSystem = "aarch64-linux"
+
+            [runner.overrides.imports]
+            "./secrets.nix" = "./tests/fake-secrets.nix"
+        "#;
+        let config = super::Config::try_from(toml_str.to_string()).unwrap();
+        check!(
+            config.runner.overrides.names()
+                == vec![
+                    "env.HOME",
+                    "builtins.currentSystem",
+                    "imports../secrets.nix"
+                ]
+        );
+    }
 }
 
 #[cfg(test)]
@@ -157,6 +233,15 @@ mod config_search_tests {
         check!(config.unwrap().runner.num_threads == NumThreads::new(8));
     }
 
+    #[rstest]
+    fn it_finds_the_project_root(path: PathBuf) {
+        create_file(&path, "project/child/a_file", b"");
+        create_file(&path, "project/flake.lock", b"");
+
+        check!(Config::find_root(&path.join("project/child")) == Some(path.join("project")));
+        check!(Config::find_root(&path.join("project")) == Some(path.join("project")));
+    }
+
     #[fixture]
     fn path() -> PathBuf {
         TempDir::new().unwrap().path().to_path_buf()
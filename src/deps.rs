@@ -0,0 +1,437 @@
+use std::{
+    collections::BTreeSet,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::files::TestFile;
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Dependencies {
+    pub files: BTreeSet<PathBuf>,
+    /// Referenced paths that don't exist, such as files deleted since.
+    pub missing: BTreeSet<PathBuf>,
+    /// False when a path is built at evaluation time (`./${name}.nix`,
+    /// `./. + "/lib.nix"`), so the files can't all be known statically.
+    pub complete: bool,
+    /// The environment variables read with `getEnv`, or `None` when a name
+    /// is built at evaluation time.
+    pub env: Option<BTreeSet<String>>,
+    /// Whether `forAll` is used, so the results depend on the seed.
+    pub properties: bool,
+}
+
+/// Finds the files a Nix file depends on by following the relative path
+/// literals (`./foo.nix`, `../lib`) it contains, transitively through the
+/// `.nix` files it references. A directory dependency stands for every `.nix`
+/// file beneath it. The result always includes the file itself.
+pub fn dependencies(file: &Path) -> Dependencies {
+    let mut files = BTreeSet::new();
+    let mut missing = BTreeSet::new();
+    let mut complete = true;
+    let mut env = Some(BTreeSet::new());
+    let mut properties = false;
+    let mut visited = BTreeSet::new();
+    let mut pending = vec![file.to_path_buf()];
+
+    while let Some(path) = pending.pop() {
+        let Ok(path) = path.canonicalize() else {
+            missing.insert(resolve_missing(&path));
+            continue;
+        };
+        if !visited.insert(path.clone()) {
+            continue;
+        }
+
+        if path.is_dir() {
+            pending.extend(nix_files(&path));
+            continue;
+        }
+
+        if path.extension().is_some_and(|ext| ext == "nix") {
+            if let (Ok(source), Some(dir)) = (fs::read_to_string(&path), path.parent()) {
+                let (literals, dynamic) = path_literals(&source);
+                pending.extend(literals.into_iter().map(|literal| dir.join(literal)));
+                complete &= !dynamic;
+                env = env.zip(env_names(&source)).map(|(mut env, names)| {
+                    env.extend(names.into_iter().map(str::to_string));
+                    env
+                });
+                properties |= mentions(&source, "forAll");
+            }
+        }
+
+        files.insert(path);
+    }
+
+    Dependencies {
+        files,
+        missing,
+        complete,
+        env,
+        properties,
+    }
+}
+
+/// Resolves a path that doesn't exist as far as it can, through its parent
+/// directory, so it compares equal to the canonical paths of existing files.
+fn resolve_missing(path: &Path) -> PathBuf {
+    match (path.parent().map(Path::canonicalize), path.file_name()) {
+        (Some(Ok(parent)), Some(name)) => parent.join(name),
+        _ => path.to_path_buf(),
+    }
+}
+
+/// Narrows test files down to those depending on one of the changed files.
+/// Returns `None` when that can't be decided: a deleted `.nix` file, which may
+/// have been imported through a directory, or a test file with dependencies
+/// that aren't known statically.
+pub fn affected_test_files(
+    test_files: Vec<TestFile>,
+    changed: &[PathBuf],
+) -> Option<Vec<TestFile>> {
+    let mut deleted = BTreeSet::new();
+    let mut existing = BTreeSet::new();
+    for path in changed {
+        match path.canonicalize() {
+            Ok(path) => {
+                existing.insert(path);
+            }
+            Err(_) if path.extension().is_some_and(|ext| ext == "nix") => return None,
+            Err(_) => {
+                deleted.insert(resolve_missing(path));
+            }
+        }
+    }
+
+    let mut affected = Vec::new();
+
+    for test_file in test_files {
+        let TestFile::Valid(path) = &test_file else {
+            affected.push(test_file);
+            continue;
+        };
+
+        let dependencies = dependencies(Path::new(path));
+        if !dependencies.complete {
+            return None;
+        }
+        if !dependencies.files.is_disjoint(&existing) || !dependencies.missing.is_disjoint(&deleted)
+        {
+            affected.push(test_file);
+        }
+    }
+
+    Some(affected)
+}
+
+fn nix_files(dir: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return vec![];
+    };
+
+    entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| {
+            let hidden = path
+                .file_name()
+                .is_some_and(|name| name.to_string_lossy().starts_with('.'));
+            !hidden && (path.is_dir() || path.extension().is_some_and(|ext| ext == "nix"))
+        })
+        .collect()
+}
+
+fn is_path_char(c: u8) -> bool {
+    c.is_ascii_alphanumeric() || matches!(c, b'.' | b'_' | b'-' | b'+' | b'/')
+}
+
+/// Returns the relative path literals in a Nix source, skipping comments, and
+/// whether any of them is extended at evaluation time.
+fn path_literals(source: &str) -> (Vec<&str>, bool) {
+    let bytes = source.as_bytes();
+    let mut literals = Vec::new();
+    let mut dynamic = false;
+    let mut i = 0;
+
+    while i < bytes.len() {
+        let rest = &source[i..];
+
+        if rest.starts_with('#') {
+            i += rest.find('\n').unwrap_or(rest.len());
+            continue;
+        }
+        if let Some(comment) = rest.strip_prefix("/*") {
+            i += comment.find("*/").map_or(rest.len(), |end| end + 4);
+            continue;
+        }
+
+        let preceded_by_path = i > 0 && (is_path_char(bytes[i - 1]) || bytes[i - 1] == b'\'');
+        if !preceded_by_path && (rest.starts_with("./") || rest.starts_with("../")) {
+            let len = rest.bytes().take_while(|&c| is_path_char(c)).count();
+            let literal = rest[..len].trim_end_matches('/');
+            if literal.len() > 2 || literal == "./." {
+                literals.push(literal);
+            }
+            let next = &rest[len..];
+            let operator = next.trim_start();
+            dynamic |= next.starts_with("${")
+                || (operator.starts_with('+') && !operator.starts_with("++"));
+            i += len;
+            continue;
+        }
+
+        i += rest.chars().next().map_or(1, char::len_utf8);
+    }
+
+    (literals, dynamic)
+}
+
+fn is_identifier_char(c: u8) -> bool {
+    c.is_ascii_alphanumeric() || matches!(c, b'_' | b'-' | b'\'')
+}
+
+/// Returns the names passed to `getEnv` as string literals in a Nix source,
+/// skipping comments, or `None` when a name is built at evaluation time.
+fn env_names(source: &str) -> Option<Vec<&str>> {
+    let bytes = source.as_bytes();
+    let mut names = Vec::new();
+    let mut i = 0;
+
+    while i < bytes.len() {
+        let rest = &source[i..];
+
+        if rest.starts_with('#') {
+            i += rest.find('\n').unwrap_or(rest.len());
+            continue;
+        }
+        if let Some(comment) = rest.strip_prefix("/*") {
+            i += comment.find("*/").map_or(rest.len(), |end| end + 4);
+            continue;
+        }
+
+        let preceded_by_identifier = i > 0 && is_identifier_char(bytes[i - 1]);
+        if let Some(call) = rest
+            .strip_prefix("getEnv")
+            .filter(|_| !preceded_by_identifier)
+        {
+            // Not a call to getEnv, but a longer name, a binding or an inherit
+            let next = call.trim_start();
+            if call.bytes().next().is_some_and(is_identifier_char) || next.starts_with(['=', ';']) {
+                i += "getEnv".len();
+                continue;
+            }
+            let name = next.strip_prefix('"')?;
+            let name = &name[..name.find('"')?];
+            if name.contains(['\\', '$']) {
+                return None;
+            }
+            names.push(name);
+            i += "getEnv".len();
+            continue;
+        }
+
+        i += rest.chars().next().map_or(1, char::len_utf8);
+    }
+
+    Some(names)
+}
+
+/// Whether a Nix source uses a name outside of comments, as a whole
+/// identifier or an attribute.
+fn mentions(source: &str, name: &str) -> bool {
+    let bytes = source.as_bytes();
+    let mut i = 0;
+
+    while i < bytes.len() {
+        let rest = &source[i..];
+
+        if rest.starts_with('#') {
+            i += rest.find('\n').unwrap_or(rest.len());
+            continue;
+        }
+        if let Some(comment) = rest.strip_prefix("/*") {
+            i += comment.find("*/").map_or(rest.len(), |end| end + 4);
+            continue;
+        }
+
+        let preceded_by_identifier = i > 0 && is_identifier_char(bytes[i - 1]);
+        if let Some(next) = rest.strip_prefix(name).filter(|_| !preceded_by_identifier) {
+            if !next.bytes().next().is_some_and(is_identifier_char) {
+                return true;
+            }
+        }
+
+        i += rest.chars().next().map_or(1, char::len_utf8);
+    }
+
+    false
+}
+
+#[cfg(test)]
+mod deps_tests {
+    use std::fs;
+
+    use assert2::check;
+    use tempfile::TempDir;
+
+    use super::*;
+
+    #[test]
+    fn it_finds_path_literals() {
+        let source = r#"
+{ pkgs ? import <nixpkgs> { }, nix-tests }:
+let
+  lib = import ./lib.nix;
+  # import ./commented.nix
+  /* import ../also-commented.nix */
+  data = builtins.readFile ../data/input.json;
+  all = ./.;
+in
+"https://example.com/a/b" + "${./module}/"
+"#;
+
+        check!(
+            path_literals(source)
+                == (
+                    vec!["./lib.nix", "../data/input.json", "./.", "./module"],
+                    false
+                )
+        );
+    }
+
+    #[test]
+    fn it_detects_dynamic_paths() {
+        check!(path_literals("import ./modules/${name}.nix").1);
+        check!(path_literals("import (./. + \"/lib.nix\")").1);
+        check!(!path_literals("[ ./a.nix ./b.nix ]").1);
+        check!(!path_literals("[ ./a.nix ] ++ [ ./b.nix ]").1);
+    }
+
+    #[test]
+    fn it_finds_environment_variables() {
+        let source = r#"
+let
+  home = builtins.getEnv "HOME";
+  # builtins.getEnv "COMMENTED"
+  user = with builtins; getEnv  "USER";
+  myGetEnv = name: name;
+  inherit (builtins) getEnv;
+in
+myGetEnv "NOT_READ"
+"#;
+
+        check!(env_names(source) == Some(vec!["HOME", "USER"]));
+    }
+
+    #[test]
+    fn it_detects_dynamic_environment_variables() {
+        check!(env_names("builtins.getEnv name") == None);
+        check!(env_names("builtins.getEnv \"NIX_${name}\"") == None);
+        check!(env_names("map builtins.getEnv [ \"HOME\" ]") == None);
+    }
+
+    #[test]
+    fn it_finds_uses_of_a_name() {
+        check!(mentions("helpers.forAll gen.ints (n: true)", "forAll"));
+        check!(mentions("inherit (helpers) forAll;", "forAll"));
+        check!(!mentions("# helpers.forAll\n/* forAll */ 1", "forAll"));
+        check!(!mentions("forAllCases = 1; myforAll = 2;", "forAll"));
+    }
+
+    #[test]
+    fn it_follows_imports_transitively() {
+        let dir = TempDir::new().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        fs::create_dir_all(root.join("lib/nested")).unwrap();
+        fs::write(root.join("my_test.nix"), "import ./lib/default.nix").unwrap();
+        fs::write(root.join("lib/default.nix"), "import ./helper.nix ./nested").unwrap();
+        fs::write(root.join("lib/helper.nix"), "{ data = ../data.json; }").unwrap();
+        fs::write(root.join("lib/nested/a.nix"), "{ }").unwrap();
+        fs::write(root.join("lib/nested/notes.txt"), "").unwrap();
+        fs::write(root.join("data.json"), "{}").unwrap();
+        fs::write(root.join("unrelated.nix"), "{ }").unwrap();
+
+        check!(
+            dependencies(&root.join("my_test.nix")).files
+                == BTreeSet::from([
+                    root.join("data.json"),
+                    root.join("lib/default.nix"),
+                    root.join("lib/helper.nix"),
+                    root.join("lib/nested/a.nix"),
+                    root.join("my_test.nix"),
+                ])
+        );
+    }
+
+    #[test]
+    fn it_ignores_missing_dependencies() {
+        let dir = TempDir::new().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        fs::write(root.join("my_test.nix"), "import ./missing.nix").unwrap();
+
+        check!(
+            dependencies(&root.join("my_test.nix")).files
+                == BTreeSet::from([root.join("my_test.nix")])
+        );
+    }
+
+    #[test]
+    fn it_selects_affected_test_files() {
+        let dir = TempDir::new().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        fs::write(root.join("a_test.nix"), "import ./lib.nix").unwrap();
+        fs::write(root.join("b_test.nix"), "{ }").unwrap();
+        fs::write(root.join("lib.nix"), "{ }").unwrap();
+
+        let test_files = vec![
+            TestFile::Valid(root.join("a_test.nix").to_string_lossy().to_string()),
+            TestFile::Valid(root.join("b_test.nix").to_string_lossy().to_string()),
+            TestFile::NotFound("missing_test.nix".to_string()),
+        ];
+
+        check!(
+            affected_test_files(test_files, &[root.join("lib.nix")])
+                == Some(vec![
+                    TestFile::Valid(root.join("a_test.nix").to_string_lossy().to_string()),
+                    TestFile::NotFound("missing_test.nix".to_string()),
+                ])
+        );
+    }
+
+    #[test]
+    fn it_gives_up_on_unknown_dependencies() {
+        let dir = TempDir::new().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        fs::write(root.join("a_test.nix"), "import ./${\"lib\"}.nix").unwrap();
+        fs::write(root.join("lib.nix"), "{ }").unwrap();
+
+        let test_files = vec![TestFile::Valid(
+            root.join("a_test.nix").to_string_lossy().to_string(),
+        )];
+
+        check!(affected_test_files(test_files, &[root.join("lib.nix")]) == None);
+    }
+
+    #[test]
+    fn it_handles_deleted_files() {
+        let dir = TempDir::new().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        fs::write(root.join("a_test.nix"), "builtins.readFile ./data.json").unwrap();
+        fs::write(root.join("b_test.nix"), "{ }").unwrap();
+
+        let test_files = vec![
+            TestFile::Valid(root.join("a_test.nix").to_string_lossy().to_string()),
+            TestFile::Valid(root.join("b_test.nix").to_string_lossy().to_string()),
+        ];
+
+        check!(
+            affected_test_files(test_files.clone(), &[root.join("data.json")])
+                == Some(vec![TestFile::Valid(
+                    root.join("a_test.nix").to_string_lossy().to_string()
+                )])
+        );
+        check!(affected_test_files(test_files.clone(), &[root.join("notes.txt")]) == Some(vec![]));
+        check!(affected_test_files(test_files, &[root.join("deleted.nix")]) == None);
+    }
+}
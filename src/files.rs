@@ -1,7 +1,9 @@
 use std::cmp::Ordering;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::process::Command;
 
+use anyhow::{bail, Context};
+
 #[derive(Debug, Eq, Clone)]
 pub enum TestFile {
     Valid(String),
@@ -137,6 +139,42 @@ impl SearchTestFiles for FindSearchTestFiles {
     }
 }
 
+/// Lists the files changed in the working tree since a git revision,
+/// including untracked ones, as absolute paths.
+pub fn changed_files(since: &str) -> anyhow::Result<Vec<PathBuf>> {
+    changed_files_in(Path::new("."), since)
+}
+
+fn changed_files_in(dir: &Path, since: &str) -> anyhow::Result<Vec<PathBuf>> {
+    let git = |args: &[&str]| -> anyhow::Result<String> {
+        let output = Command::new("git")
+            .args(args)
+            .current_dir(dir)
+            .output()
+            .context("Failed to execute git")?;
+        if !output.status.success() {
+            bail!(
+                "git {} failed: {}",
+                args.join(" "),
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).to_string())
+    };
+
+    let root = PathBuf::from(git(&["rev-parse", "--show-toplevel"])?.trim());
+    // A renamed file is listed under both of its paths, so the tests that
+    // still import the old one are found
+    let changed = git(&["diff", "--name-only", "--no-renames", since, "--"])?;
+    let untracked = git(&["ls-files", "--others", "--exclude-standard", "--full-name"])?;
+
+    Ok(changed
+        .lines()
+        .chain(untracked.lines())
+        .map(|file| root.join(file))
+        .collect())
+}
+
 #[cfg(test)]
 mod files_tests {
     use std::fs::{self, File};
@@ -266,7 +304,40 @@ mod files_tests {
         TempDir::new().unwrap().path().to_path_buf()
     }
 
-    fn create_file(base: &PathBuf, relative_path: &str) {
+    #[test]
+    fn it_lists_both_paths_of_renamed_files() {
+        let dir = TempDir::new().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        let git = |args: &[&str]| {
+            let status = Command::new("git")
+                .args(["-c", "user.name=test", "-c", "user.email=test@example.com"])
+                .args(["-c", "commit.gpgsign=false"])
+                .args(args)
+                .current_dir(&root)
+                .output()
+                .unwrap()
+                .status;
+            check!(status.success());
+        };
+        fs::write(root.join("my_test.nix"), "import ./lib.nix").unwrap();
+        fs::write(root.join("lib.nix"), "{ }").unwrap();
+        git(&["init", "--quiet"]);
+        git(&["add", "."]);
+        git(&["commit", "--quiet", "-m", "initial"]);
+        git(&["mv", "lib.nix", "renamed.nix"]);
+
+        let changed = changed_files_in(&root, "HEAD").unwrap();
+
+        check!(changed == [root.join("lib.nix"), root.join("renamed.nix")]);
+        check!(
+            crate::deps::affected_test_files(
+                vec![TestFile::Valid(path!(root, "my_test.nix"))],
+                &changed
+            ) == None
+        );
+    }
+
+    fn create_file(base: &Path, relative_path: &str) {
         let file_path = base.join(relative_path);
         if let Some(parent) = file_path.parent() {
             fs::create_dir_all(parent).unwrap();
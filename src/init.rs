@@ -0,0 +1,115 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+
+const CONFIG: &str = r#"# Run `nix-tests config show` to see every option with its current value.
+
+[runner]
+# Number of test files evaluated at the same time, defaults to the number of CPUs
+# num-threads = 4
+# Milliseconds before a test file is stopped, 0 for no limit
+# timeout = 0
+
+[report]
+# "auto" (github in GitHub Actions, human otherwise), "human", "json", "quickfix", "github", "html" or "markdown"
+# format = "auto"
+# hide-succeeded = false
+# File to write the report to instead of stdout
+# output = "report.html"
+"#;
+
+const EXAMPLE_TEST: &str = r#"{
+  pkgs ? import <nixpkgs> { },
+  nix-tests,
+}:
+nix-tests.runTests {
+  "example" = helpers: {
+    "addition works" = helpers.isEq (1 + 1) 2;
+    "lists can be sorted" = helpers.isEq (builtins.sort builtins.lessThan [ 3 1 2 ]) [ 1 2 3 ];
+    "nixpkgs has hello" = helpers.hasAttr "hello" pkgs;
+  };
+}
+"#;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Scaffolded {
+    Created(PathBuf),
+    /// The file already existed and was left untouched
+    Skipped(PathBuf),
+}
+
+/// Creates a `.nix-tests.toml` and an example test file in a directory,
+/// keeping any that already exist.
+pub fn init(dir: &Path) -> anyhow::Result<Vec<Scaffolded>> {
+    fs::create_dir_all(dir).context(format!("Failed to create directory: {}", dir.display()))?;
+
+    [
+        (".nix-tests.toml", CONFIG),
+        ("example_test.nix", EXAMPLE_TEST),
+    ]
+    .into_iter()
+    .map(|(name, content)| {
+        let path = dir.join(name);
+        if path.exists() {
+            return Ok(Scaffolded::Skipped(path));
+        }
+        fs::write(&path, content).context(format!("Failed to write file: {}", path.display()))?;
+        Ok(Scaffolded::Created(path))
+    })
+    .collect()
+}
+
+#[cfg(test)]
+mod init_tests {
+    use assert2::check;
+    use tempfile::TempDir;
+
+    use super::*;
+    use crate::config::Config;
+
+    #[test]
+    fn it_scaffolds_config_and_example_test() {
+        let dir = TempDir::new().unwrap();
+
+        check!(
+            init(dir.path()).unwrap()
+                == [
+                    Scaffolded::Created(dir.path().join(".nix-tests.toml")),
+                    Scaffolded::Created(dir.path().join("example_test.nix")),
+                ]
+        );
+        check!(fs::read_to_string(dir.path().join("example_test.nix")).unwrap() == EXAMPLE_TEST);
+    }
+
+    #[test]
+    fn it_keeps_existing_files() {
+        let dir = TempDir::new().unwrap();
+        fs::write(dir.path().join(".nix-tests.toml"), "[runner]\n").unwrap();
+
+        check!(
+            init(dir.path()).unwrap()
+                == [
+                    Scaffolded::Skipped(dir.path().join(".nix-tests.toml")),
+                    Scaffolded::Created(dir.path().join("example_test.nix")),
+                ]
+        );
+        check!(fs::read_to_string(dir.path().join(".nix-tests.toml")).unwrap() == "[runner]\n");
+    }
+
+    #[test]
+    fn it_suggests_known_options() {
+        let uncommented = CONFIG
+            .lines()
+            .map(|line| match line.strip_prefix("# ") {
+                Some(option) if option.contains(" = ") => option,
+                _ => line,
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        check!(Config::unknown_keys(&uncommented).unwrap().is_empty());
+    }
+}
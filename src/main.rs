@@ -1,26 +1,49 @@
-use std::{path::Path, sync::Arc};
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+};
 
-use anyhow::bail;
-use clap::{Args as ClapArgs, Parser, ValueEnum};
+use anyhow::{bail, Context};
+use clap::{Args as ClapArgs, Parser, Subcommand, ValueEnum};
 use tokio::signal::unix::{signal, SignalKind};
 
 use crate::{
     config::Config,
-    files::{FindSearchTestFiles, RgSearchTestFiles, SearchTestFiles, TestFile},
-    reports::{config as report_config, ConfigurableReporter},
-    runners::{config as runner_config, NixTestRunner, TestSuiteRunner},
+    deps::affected_test_files,
+    files::{changed_files, FindSearchTestFiles, RgSearchTestFiles, SearchTestFiles, TestFile},
+    init::Scaffolded,
+    reports::{
+        config as report_config, json_schema, ConfigurableReporter, ReportEvent, Reporter,
+        TestFileListing,
+    },
+    runners::{
+        config as runner_config, CachingRunner, NixTestRunner, ResultCache, TestSuiteRunner,
+        Timings,
+    },
+    shard::Shard,
 };
 
 mod config;
+mod deps;
 mod files;
+mod init;
+mod merge;
+mod repl;
 mod reports;
 mod runners;
+mod serve;
+mod shard;
 
 #[derive(Debug, Clone, ValueEnum, PartialEq, Eq)]
 #[value(rename_all = "kebab-case")]
 pub enum Format {
     Json,
     Human,
+    Quickfix,
+    Github,
+    Auto,
+    Html,
+    Markdown,
 }
 
 impl From<Format> for report_config::Format {
@@ -28,6 +51,68 @@ impl From<Format> for report_config::Format {
         match value {
             Format::Json => report_config::Format::Json,
             Format::Human => report_config::Format::Human,
+            Format::Quickfix => report_config::Format::Quickfix,
+            Format::Github => report_config::Format::Github,
+            Format::Auto => report_config::Format::Auto,
+            Format::Html => report_config::Format::Html,
+            Format::Markdown => report_config::Format::Markdown,
+        }
+    }
+}
+
+#[derive(Debug, Clone, ValueEnum, PartialEq, Eq)]
+#[value(rename_all = "kebab-case")]
+pub enum ListFormat {
+    Human,
+    Json,
+}
+
+#[derive(Debug, Clone, ValueEnum, PartialEq, Eq)]
+#[value(rename_all = "kebab-case")]
+pub enum ShardBy {
+    Hash,
+    Timings,
+}
+
+impl From<ShardBy> for shard::Strategy {
+    fn from(value: ShardBy) -> Self {
+        match value {
+            ShardBy::Hash => shard::Strategy::Hash,
+            ShardBy::Timings => shard::Strategy::Timings,
+        }
+    }
+}
+
+#[derive(Debug, Clone, ValueEnum, PartialEq, Eq)]
+#[value(rename_all = "kebab-case")]
+pub enum Order {
+    Sorted,
+    Random,
+    SlowestFirst,
+}
+
+impl From<Order> for runner_config::Order {
+    fn from(value: Order) -> Self {
+        match value {
+            Order::Sorted => runner_config::Order::Sorted,
+            Order::Random => runner_config::Order::Random,
+            Order::SlowestFirst => runner_config::Order::SlowestFirst,
+        }
+    }
+}
+
+#[derive(Debug, Clone, ValueEnum, PartialEq, Eq)]
+#[value(rename_all = "kebab-case")]
+pub enum Evaluator {
+    Instantiate,
+    Repl,
+}
+
+impl From<Evaluator> for runner_config::Evaluator {
+    fn from(value: Evaluator) -> Self {
+        match value {
+            Evaluator::Instantiate => runner_config::Evaluator::Instantiate,
+            Evaluator::Repl => runner_config::Evaluator::Repl,
         }
     }
 }
@@ -47,6 +132,95 @@ pub struct RunnerArgs {
         help = "Timeout in milliseconds for each test file (0 for no timeout)"
     )]
     timeout: Option<u64>,
+
+    #[arg(
+        long,
+        help = "Seed for property tests and --order random (default: random, printed on failure)"
+    )]
+    seed: Option<u32>,
+
+    #[arg(long, help = "Number of generated inputs for each property test")]
+    property_iterations: Option<u64>,
+
+    #[arg(
+        long,
+        help = "Timeout in milliseconds for each derivation built by build checks (0 for no timeout)"
+    )]
+    build_timeout: Option<u64>,
+
+    #[arg(
+        long,
+        help = "Number of derivations built concurrently by build checks (1-1024, default: 1)",
+        value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..=1024)
+    )]
+    build_jobs: Option<usize>,
+
+    #[arg(
+        long,
+        help = "Path to the nix-tests Nix library (default: $NIX_TESTS_LIB_PATH or the bundled library)"
+    )]
+    lib_path: Option<String>,
+
+    #[arg(
+        long,
+        help = "Number of test files evaluated together by a single Nix invocation (1-10000, default: 1)",
+        value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..=10000)
+    )]
+    batch_size: Option<usize>,
+
+    #[arg(
+        long,
+        value_enum,
+        help = "How test files are evaluated: a nix-instantiate process per file, or a pool of persistent nix repl processes"
+    )]
+    evaluator: Option<Evaluator>,
+
+    #[arg(
+        long,
+        help = "Reuse results of test files whose contents and dependencies are unchanged",
+        value_name = "BOOL",
+        default_missing_value = "true"
+    )]
+    cache: Option<bool>,
+
+    #[arg(long, help = "Bypass the result cache for this run")]
+    no_cache: bool,
+
+    #[arg(
+        long,
+        help = "Directory for cached results (default: $XDG_CACHE_HOME/nix-tests/results)"
+    )]
+    cache_dir: Option<String>,
+
+    #[arg(
+        long,
+        help = "Maximum address space in MiB for each Nix evaluator process"
+    )]
+    max_memory: Option<u64>,
+
+    #[arg(
+        long,
+        help = "Maximum CPU time in seconds for each Nix evaluator process"
+    )]
+    max_cpu_time: Option<u64>,
+
+    #[arg(long, help = "Maximum open files for each Nix evaluator process")]
+    max_open_files: Option<u64>,
+
+    #[arg(
+        long,
+        help = "Measure the evaluation time of each test by evaluating tests one at a time",
+        value_name = "BOOL",
+        default_missing_value = "true"
+    )]
+    profile: Option<bool>,
+
+    #[arg(
+        long,
+        value_enum,
+        help = "Order to run test files in: sorted by path, shuffled with the seed, or slowest first by previous timings"
+    )]
+    order: Option<Order>,
 }
 
 #[derive(Default, Debug, ClapArgs, Clone)]
@@ -78,6 +252,35 @@ pub struct ReportArgs {
         default_missing_value = "true"
     )]
     hide_errored: Option<bool>,
+
+    #[arg(
+        long,
+        help = "Show resource usage of each test file and in the summary",
+        value_name = "BOOL",
+        default_missing_value = "true"
+    )]
+    stats: Option<bool>,
+
+    #[arg(
+        long,
+        value_name = "N",
+        help = "Show the N slowest test files in the summary"
+    )]
+    slowest: Option<usize>,
+
+    #[arg(
+        long,
+        value_name = "MS",
+        help = "Warn about test files taking longer than this many milliseconds (0 to disable)"
+    )]
+    warn_slower_than: Option<u64>,
+
+    #[arg(
+        long,
+        value_name = "PATH",
+        help = "Write the report to a file instead of stdout"
+    )]
+    output: Option<String>,
 }
 
 #[derive(Default, Debug, ClapArgs, Clone)]
@@ -99,6 +302,46 @@ impl ConfigArgs {
                     .map(Into::into)
                     .unwrap_or(base.runner.num_threads),
                 timeout: self.runner.timeout.unwrap_or(base.runner.timeout),
+                seed: self.runner.seed.or(base.runner.seed),
+                property_iterations: self
+                    .runner
+                    .property_iterations
+                    .map(Into::into)
+                    .unwrap_or(base.runner.property_iterations),
+                build_timeout: self
+                    .runner
+                    .build_timeout
+                    .unwrap_or(base.runner.build_timeout),
+                build_jobs: self
+                    .runner
+                    .build_jobs
+                    .map(Into::into)
+                    .unwrap_or(base.runner.build_jobs),
+                overrides: base.runner.overrides,
+                lib_path: self.runner.lib_path.clone().or(base.runner.lib_path),
+                evaluator: self
+                    .runner
+                    .evaluator
+                    .clone()
+                    .map(Into::into)
+                    .unwrap_or(base.runner.evaluator),
+                batch_size: self
+                    .runner
+                    .batch_size
+                    .map(Into::into)
+                    .unwrap_or(base.runner.batch_size),
+                cache: !self.runner.no_cache && self.runner.cache.unwrap_or(base.runner.cache),
+                cache_dir: self.runner.cache_dir.clone().or(base.runner.cache_dir),
+                max_memory: self.runner.max_memory.or(base.runner.max_memory),
+                max_cpu_time: self.runner.max_cpu_time.or(base.runner.max_cpu_time),
+                max_open_files: self.runner.max_open_files.or(base.runner.max_open_files),
+                profile: self.runner.profile.unwrap_or(base.runner.profile),
+                order: self
+                    .runner
+                    .order
+                    .clone()
+                    .map(Into::into)
+                    .unwrap_or(base.runner.order),
             },
             report: report_config::Config {
                 format: self
@@ -113,6 +356,13 @@ impl ConfigArgs {
                     .unwrap_or(base.report.hide_succeeded),
                 hide_failed: self.report.hide_failed.unwrap_or(base.report.hide_failed),
                 hide_errored: self.report.hide_errored.unwrap_or(base.report.hide_errored),
+                stats: self.report.stats.unwrap_or(base.report.stats),
+                slowest: self.report.slowest.unwrap_or(base.report.slowest),
+                warn_slower_than: self
+                    .report
+                    .warn_slower_than
+                    .unwrap_or(base.report.warn_slower_than),
+                output: self.report.output.clone().or(base.report.output),
             },
         }
     }
@@ -126,12 +376,60 @@ impl ConfigArgs {
     Requires either 'rg' (ripgrep, preferred) or 'find' to discover test files.\n\
     Automatically uses 'rg' if available, otherwise falls back to 'find'."
 )]
+#[command(args_conflicts_with_subcommands = true)]
 struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    #[command(flatten)]
+    run: RunArgs,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Run test files (the default when no command is given)
+    Run(Box<RunArgs>),
+    /// List test files, and with --tests the tests in them
+    List(ListArgs),
+    /// Show or validate the configuration
+    #[command(subcommand)]
+    Config(ConfigCommand),
+    /// Create a .nix-tests.toml and an example test file
+    Init(InitArgs),
+    /// Merge the JSON output of several runs, such as shards, into one report
+    Merge(MergeArgs),
+    /// Print the JSON Schema of a line of the --format json output
+    Schema,
+    /// Serve test discovery and runs to an editor over JSON-RPC on stdio
+    Serve(ServeArgs),
+}
+
+#[derive(Debug, ClapArgs)]
+struct RunArgs {
     #[arg(long, help = "Path to the configuration directory or file")]
     config: Option<String>,
 
-    #[arg(long, help = "Show the loaded config and exit")]
-    show: bool,
+    #[arg(
+        long,
+        value_name = "REF",
+        help = "Only run test files depending on files changed since a git revision"
+    )]
+    changed_since: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "INDEX/TOTAL",
+        help = "Only run one of TOTAL parts of the test files, numbered from 1"
+    )]
+    shard: Option<Shard>,
+
+    #[arg(
+        long,
+        value_enum,
+        default_value = "hash",
+        help = "Partition test files for --shard by a hash of their path, or balanced by timings of previous runs"
+    )]
+    shard_by: ShardBy,
 
     #[command(flatten)]
     config_args: ConfigArgs,
@@ -140,27 +438,163 @@ struct Args {
     paths: Vec<String>,
 }
 
+#[derive(Debug, ClapArgs)]
+struct ListArgs {
+    #[arg(long, help = "Path to the configuration directory or file")]
+    config: Option<String>,
+
+    #[arg(
+        long,
+        help = "Also list the tests in each file, without running their checks"
+    )]
+    tests: bool,
+
+    #[arg(
+        long,
+        value_enum,
+        default_value = "human",
+        help = "Output format of the listing, JSON prints a line per test file"
+    )]
+    format: ListFormat,
+
+    #[command(flatten)]
+    runner: RunnerArgs,
+
+    #[arg(default_value = ".", num_args = 0..)]
+    paths: Vec<String>,
+}
+
+#[derive(Debug, Subcommand)]
+enum ConfigCommand {
+    /// Show the loaded configuration, including command line options
+    Show(Box<ConfigShowArgs>),
+    /// Check the configuration file for errors and unknown keys
+    Validate(ConfigValidateArgs),
+}
+
+#[derive(Debug, ClapArgs)]
+struct ConfigShowArgs {
+    #[arg(long, help = "Path to the configuration directory or file")]
+    config: Option<String>,
+
+    #[command(flatten)]
+    config_args: ConfigArgs,
+}
+
+#[derive(Debug, ClapArgs)]
+struct ConfigValidateArgs {
+    #[arg(long, help = "Path to the configuration directory or file")]
+    config: Option<String>,
+}
+
+#[derive(Debug, ClapArgs)]
+struct InitArgs {
+    #[arg(default_value = ".", help = "Directory to create the files in")]
+    dir: PathBuf,
+}
+
+#[derive(Debug, ClapArgs)]
+struct ServeArgs {
+    #[arg(long, help = "Path to the configuration directory or file")]
+    config: Option<String>,
+
+    #[command(flatten)]
+    runner: RunnerArgs,
+}
+
+#[derive(Debug, ClapArgs)]
+struct MergeArgs {
+    #[arg(long, help = "Path to the configuration directory or file")]
+    config: Option<String>,
+
+    #[command(flatten)]
+    report: ReportArgs,
+
+    #[arg(required = true, help = "Files with the output of --format json")]
+    files: Vec<String>,
+}
+
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     let args = Args::parse();
 
-    let config = load_config(&args)?;
+    match args.command.unwrap_or(Command::Run(Box::new(args.run))) {
+        Command::Run(run_args) => run(*run_args).await,
+        Command::List(list_args) => list(list_args).await,
+        Command::Config(ConfigCommand::Show(show_args)) => show_config(*show_args),
+        Command::Config(ConfigCommand::Validate(validate_args)) => validate_config(validate_args),
+        Command::Init(init_args) => init(init_args),
+        Command::Merge(merge_args) => merge_reports(merge_args),
+        Command::Schema => {
+            print!("{}", json_schema());
+            Ok(())
+        }
+        Command::Serve(serve_args) => serve(serve_args).await,
+    }
+}
+
+async fn run(args: RunArgs) -> anyhow::Result<()> {
+    let mut config = load_config(args.config.as_deref(), &args.config_args)?;
 
-    if args.show {
-        println!("{}", toml::to_string(&config)?);
-        return Ok(());
+    // Results of property tests are only cached with a configured seed, as
+    // a random one is meant to try new inputs on every run
+    let configured_seed = config.runner.seed;
+    config
+        .runner
+        .seed
+        .get_or_insert_with(runner_config::random_seed);
+
+    if config.runner.profile && config.runner.batch_size.get() > 1 {
+        eprintln!("Warning: profiling evaluates each test file on its own, ignoring batch-size");
+    }
+
+    let mut test_files = find_files(args.paths)?;
+
+    if let Some(since) = &args.changed_since {
+        let changed = changed_files(since)?;
+        match affected_test_files(test_files.clone(), &changed) {
+            Some(affected) => test_files = affected,
+            None => {
+                eprintln!("Dependencies of some test files are unknown, running all test files")
+            }
+        }
     }
 
-    let test_files = find_files(args.paths)?;
+    let timings_path = Timings::path(&config::cache_dir(), &project_root(args.config.as_deref())?);
+    let mut timings = Timings::load(&timings_path);
+
+    if let Some(shard) = args.shard {
+        test_files = shard.select(test_files, args.shard_by.into(), &timings);
+    }
+
+    let nix_runner = NixTestRunner::new(&config.runner)?;
+    let cache = config.runner.cache.then(|| {
+        let dir = config
+            .runner
+            .cache_dir
+            .clone()
+            .map(PathBuf::from)
+            .unwrap_or_else(|| config::cache_dir().join("results"));
+        ResultCache::new(dir, nix_runner.fingerprint()).with_seed(configured_seed)
+    });
 
     let runner = TestSuiteRunner::new(
-        Arc::new(NixTestRunner::new(config.runner.timeout)),
-        ConfigurableReporter::new(&config.report),
+        Arc::new(CachingRunner::new(nix_runner, cache)),
+        ConfigurableReporter::new(&config.report)?,
         config.runner,
-    );
+    )
+    .with_timings(timings.clone());
+    let runner = match args.shard {
+        Some(shard) => runner.with_shard(shard),
+        None => runner,
+    };
 
     tokio::select! {
         report = runner.run(&test_files) => {
+            timings.record(report.reports());
+            if let Err(err) = timings.save(&timings_path) {
+                eprintln!("Failed to save timings: {:#}", err);
+            }
             if report.has_issues() {
                 std::process::exit(1);
             }
@@ -173,24 +607,211 @@ async fn main() -> anyhow::Result<()> {
     Ok(())
 }
 
-fn load_config(args: &Args) -> anyhow::Result<Config> {
-    let file_config = if let Some(config_path) = &args.config {
-        let path = Path::new(&config_path).canonicalize()?;
-        let config_file = path.join(".nix-tests.toml");
-        if config_file.exists() {
-            Config::try_from(config_file)?
-        } else {
-            Config::search_in(&path)?.unwrap_or_default()
-        }
+async fn list(args: ListArgs) -> anyhow::Result<()> {
+    let config_args = ConfigArgs {
+        runner: args.runner,
+        ..Default::default()
+    };
+    let config = load_config(args.config.as_deref(), &config_args)?;
+
+    let runner = if args.tests {
+        Some(NixTestRunner::new(&config.runner)?)
     } else {
-        Config::search()?.unwrap_or_default()
+        None
     };
 
-    let config = args.config_args.apply_to(file_config);
+    let mut failed = false;
+    for test_file in find_files(args.paths)? {
+        let file = match test_file {
+            TestFile::NotFound(path) => {
+                eprintln!("Warning: '{path}' is not found, skipping.");
+                continue;
+            }
+            TestFile::Invalid(path) => {
+                eprintln!("Warning: '{path}' is not a test file, skipping.");
+                continue;
+            }
+            TestFile::Valid(path) => path,
+        };
+
+        let mut listing = TestFileListing {
+            file,
+            tests: None,
+            error: None,
+        };
+        if let Some(runner) = &runner {
+            match runner.list_tests(&listing.file).await {
+                Ok(tests) => listing.tests = Some(tests),
+                Err(error) => {
+                    failed = true;
+                    listing.error = Some(error);
+                }
+            }
+        }
+
+        match args.format {
+            ListFormat::Human => {
+                print!("{}", listing.human());
+                if let Some(error) = &listing.error {
+                    eprintln!("ERROR: Failed to list tests of {}: {}", listing.file, error);
+                }
+            }
+            ListFormat::Json => print!("{}", listing.json()),
+        }
+    }
+
+    if failed {
+        std::process::exit(1);
+    }
+
+    Ok(())
+}
+
+async fn serve(args: ServeArgs) -> anyhow::Result<()> {
+    let config_args = ConfigArgs {
+        runner: args.runner,
+        ..Default::default()
+    };
+    let config = load_config(args.config.as_deref(), &config_args)?;
+    // A long-lived repl would keep returning the test files it imported
+    // before they were edited
+    let runner_config = runner_config::Config {
+        evaluator: runner_config::Evaluator::Instantiate,
+        ..config.runner
+    };
+
+    serve::serve(
+        NixTestRunner::new(&runner_config)?,
+        tokio::io::BufReader::new(tokio::io::stdin()),
+        tokio::io::stdout(),
+    )
+    .await
+}
+
+fn show_config(args: ConfigShowArgs) -> anyhow::Result<()> {
+    let config = load_config(args.config.as_deref(), &args.config_args)?;
+    println!("{}", toml::to_string(&config)?);
+    Ok(())
+}
+
+fn validate_config(args: ConfigValidateArgs) -> anyhow::Result<()> {
+    let Some(config_file) = find_config_file(args.config.as_deref())? else {
+        println!("No config file found, using the default configuration");
+        return Ok(());
+    };
+
+    let content = std::fs::read_to_string(&config_file).context(format!(
+        "Failed to read config file: {}",
+        config_file.display()
+    ))?;
+    let unknown = Config::unknown_keys(&content)
+        .context(format!("Invalid config file: {}", config_file.display()))?;
+
+    if !unknown.is_empty() {
+        for key in &unknown {
+            eprintln!("ERROR: Unknown key '{}'", key);
+        }
+        bail!(
+            "Invalid config file: {}: {} unknown key(s)",
+            config_file.display(),
+            unknown.len()
+        );
+    }
+
+    println!("{} is valid", config_file.display());
+    Ok(())
+}
+
+fn init(args: InitArgs) -> anyhow::Result<()> {
+    for scaffolded in init::init(&args.dir)? {
+        match scaffolded {
+            Scaffolded::Created(path) => println!("Created {}", path.display()),
+            Scaffolded::Skipped(path) => println!("Skipped {}, it already exists", path.display()),
+        }
+    }
+    Ok(())
+}
+
+fn merge_reports(args: MergeArgs) -> anyhow::Result<()> {
+    let config_args = ConfigArgs {
+        report: args.report,
+        ..Default::default()
+    };
+    let config = load_config(args.config.as_deref(), &config_args)?;
+
+    let outputs = args
+        .files
+        .into_iter()
+        .map(|file| {
+            let output = std::fs::read_to_string(&file)
+                .context(format!("Failed to read test results: {}", file))?;
+            Ok((file, output))
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    let merged = merge::merge(&outputs)?;
+    for warning in &merged.warnings {
+        eprintln!("Warning: {}", warning);
+    }
+
+    let reporter = ConfigurableReporter::new(&config.report)?;
+    for report in merged.report.reports() {
+        if let Some(message) = reporter.on(&ReportEvent::TestFileCompleted(report.clone())) {
+            print!("{}", message);
+        }
+    }
+    let has_issues = merged.report.has_issues();
+    if let Some(message) = reporter.on(&ReportEvent::TestSuiteCompleted(merged.report)) {
+        print!("{}", message);
+    }
+
+    if has_issues {
+        std::process::exit(1);
+    }
+
+    Ok(())
+}
+
+fn load_config(config_path: Option<&str>, config_args: &ConfigArgs) -> anyhow::Result<Config> {
+    let file_config = match find_config_file(config_path)? {
+        Some(config_file) => Config::try_from(config_file)?,
+        None => Config::default(),
+    };
+
+    let config = config_args.apply_to(file_config);
 
     Ok(config)
 }
 
+fn find_config_file(config_path: Option<&str>) -> anyhow::Result<Option<PathBuf>> {
+    let dir = match config_path {
+        Some(config_path) => {
+            let path = Path::new(config_path)
+                .canonicalize()
+                .context(format!("Config path not found: {}", config_path))?;
+            if path.is_file() {
+                return Ok(Some(path));
+            }
+            path
+        }
+        None => std::env::current_dir().context("Failed to get current working directory")?,
+    };
+
+    Ok(Config::find_in(&dir))
+}
+
+/// Returns the directory of the config file, or of the project the current
+/// directory is in when there is none.
+fn project_root(config_path: Option<&str>) -> anyhow::Result<PathBuf> {
+    if let Some(config_file) = find_config_file(config_path)? {
+        if let Some(dir) = config_file.parent() {
+            return Ok(dir.to_path_buf());
+        }
+    }
+    let cwd = std::env::current_dir().context("Failed to get current working directory")?;
+    Ok(Config::find_root(&cwd).unwrap_or(cwd))
+}
+
 fn find_files(paths: Vec<String>) -> anyhow::Result<Vec<TestFile>> {
     let searcher: Box<dyn SearchTestFiles> = if command_exists("rg") {
         Box::new(RgSearchTestFiles)
@@ -230,7 +851,7 @@ mod config_args_tests {
 
     use crate::{
         config::Config, reports::config as report_config, runners::config as runner_config,
-        ConfigArgs, Format, ReportArgs, RunnerArgs,
+        ConfigArgs, Evaluator, Format, Order, ReportArgs, RunnerArgs,
     };
 
     #[test]
@@ -239,19 +860,24 @@ mod config_args_tests {
             runner: runner_config::Config {
                 num_threads: runner_config::NumThreads::new(8),
                 timeout: 0,
+                ..Default::default()
             },
             report: report_config::Config {
-                format: report_config::Format::Human,
+                format: report_config::Format::Auto,
                 hide_succeeded: false,
                 hide_failed: false,
                 hide_errored: false,
+                stats: false,
+                slowest: 0,
+                warn_slower_than: 0,
+                output: None,
             },
         };
 
         let args = ConfigArgs {
             runner: RunnerArgs {
                 num_threads: Some(4),
-                timeout: None,
+                ..Default::default()
             },
             ..Default::default()
         };
@@ -263,12 +889,17 @@ mod config_args_tests {
                     runner: runner_config::Config {
                         num_threads: runner_config::NumThreads::new(4),
                         timeout: 0,
+                        ..Default::default()
                     },
                     report: report_config::Config {
-                        format: report_config::Format::Human,
+                        format: report_config::Format::Auto,
                         hide_succeeded: false,
                         hide_failed: false,
                         hide_errored: false,
+                        stats: false,
+                        slowest: 0,
+                        warn_slower_than: 0,
+                        output: None,
                     }
                 }
         );
@@ -280,12 +911,17 @@ mod config_args_tests {
             runner: runner_config::Config {
                 num_threads: runner_config::NumThreads::new(8),
                 timeout: 0,
+                ..Default::default()
             },
             report: report_config::Config {
                 format: report_config::Format::Json,
                 hide_succeeded: false,
                 hide_failed: false,
                 hide_errored: false,
+                stats: false,
+                slowest: 0,
+                warn_slower_than: 0,
+                output: None,
             },
         };
 
@@ -301,12 +937,17 @@ mod config_args_tests {
             runner: runner_config::Config {
                 num_threads: runner_config::NumThreads::new(8),
                 timeout: 0,
+                ..Default::default()
             },
             report: report_config::Config {
-                format: report_config::Format::Human,
+                format: report_config::Format::Auto,
                 hide_succeeded: false,
                 hide_failed: false,
                 hide_errored: false,
+                stats: false,
+                slowest: 0,
+                warn_slower_than: 0,
+                output: None,
             },
         };
 
@@ -314,12 +955,31 @@ mod config_args_tests {
             runner: RunnerArgs {
                 num_threads: Some(12),
                 timeout: None,
+                seed: Some(7),
+                property_iterations: Some(500),
+                build_timeout: Some(60000),
+                build_jobs: Some(2),
+                lib_path: None,
+                evaluator: Some(Evaluator::Repl),
+                batch_size: Some(16),
+                cache: Some(true),
+                no_cache: false,
+                cache_dir: Some("/tmp/results".to_string()),
+                max_memory: Some(2048),
+                max_cpu_time: Some(60),
+                max_open_files: Some(256),
+                profile: Some(true),
+                order: Some(Order::Random),
             },
             report: ReportArgs {
                 format: Some(Format::Json),
                 hide_succeeded: Some(true),
                 hide_failed: Some(false),
                 hide_errored: Some(true),
+                stats: Some(true),
+                slowest: Some(5),
+                warn_slower_than: Some(1000),
+                output: Some("report.html".to_string()),
             },
         };
 
@@ -330,12 +990,30 @@ mod config_args_tests {
                     runner: runner_config::Config {
                         num_threads: runner_config::NumThreads::new(12),
                         timeout: 0,
+                        seed: Some(7),
+                        property_iterations: runner_config::PropertyIterations::new(500),
+                        build_timeout: 60000,
+                        build_jobs: runner_config::BuildJobs::new(2),
+                        evaluator: runner_config::Evaluator::Repl,
+                        batch_size: runner_config::BatchSize::new(16),
+                        cache: true,
+                        cache_dir: Some("/tmp/results".to_string()),
+                        max_memory: Some(2048),
+                        max_cpu_time: Some(60),
+                        max_open_files: Some(256),
+                        profile: true,
+                        order: runner_config::Order::Random,
+                        ..Default::default()
                     },
                     report: report_config::Config {
                         format: report_config::Format::Json,
                         hide_succeeded: true,
                         hide_failed: false,
                         hide_errored: true,
+                        stats: true,
+                        slowest: 5,
+                        warn_slower_than: 1000,
+                        output: Some("report.html".to_string()),
                     }
                 }
         );
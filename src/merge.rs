@@ -0,0 +1,204 @@
+use std::collections::BTreeSet;
+
+use anyhow::{bail, Context};
+
+use crate::reports::{JsonSummary, TestFileReport, TestSuiteReport, JSON_VERSION};
+
+#[derive(Debug)]
+pub struct Merged {
+    pub report: TestSuiteReport,
+    /// Results known to be missing from the merged report.
+    pub warnings: Vec<String>,
+}
+
+/// Rebuilds a single test suite report from the output of `JsonReporter` of
+/// one or more runs, such as the shards of a suite. Each output is given
+/// together with the name it's reported by in errors.
+pub fn merge(outputs: &[(String, String)]) -> anyhow::Result<Merged> {
+    let mut reports = Vec::new();
+    let mut summaries = Vec::new();
+
+    for (name, output) in outputs {
+        for (i, value) in serde_json::Deserializer::from_str(output)
+            .into_iter::<serde_json::Value>()
+            .enumerate()
+        {
+            let value = value.context(format!("Invalid JSON in {}", name))?;
+
+            let version = value.get("version").and_then(|version| version.as_u64());
+            if version.is_some_and(|version| version > JSON_VERSION as u64) {
+                bail!(
+                    "Unsupported JSON output version {} in {}",
+                    version.unwrap(),
+                    name
+                );
+            }
+            let event = value.get("event").and_then(|event| event.as_str());
+            if matches!(event, Some("test_file_not_found" | "test_file_invalid")) {
+                continue;
+            }
+
+            if value.get("status").and_then(|status| status.as_str()) == Some("summary") {
+                let summary: JsonSummary = serde_json::from_value(value).context(format!(
+                    "Invalid summary {} in {}",
+                    i + 1,
+                    name
+                ))?;
+                summaries.push(summary);
+            } else {
+                let report: TestFileReport = serde_json::from_value(value).context(format!(
+                    "Invalid test file report {} in {}",
+                    i + 1,
+                    name
+                ))?;
+                reports.push(report);
+            }
+        }
+    }
+
+    if reports.is_empty() && summaries.is_empty() {
+        bail!("No test results found");
+    }
+
+    let warnings = missing(&reports, &summaries);
+
+    // Shards run side by side, so the slowest one gives the time of the suite
+    let elapsed = summaries
+        .iter()
+        .map(|summary| summary.elapsed)
+        .max()
+        .unwrap_or_else(|| reports.iter().map(TestFileReport::elapsed).sum());
+
+    Ok(Merged {
+        report: TestSuiteReport::new(reports, elapsed),
+        warnings,
+    })
+}
+
+fn missing(reports: &[TestFileReport], summaries: &[JsonSummary]) -> Vec<String> {
+    let mut warnings = Vec::new();
+
+    let reported: BTreeSet<&str> = reports.iter().map(TestFileReport::file).collect();
+    let hidden = summaries
+        .iter()
+        .flat_map(|summary| &summary.files)
+        .filter(|file| !reported.contains(file.as_str()))
+        .collect::<BTreeSet<_>>()
+        .len();
+    if hidden > 0 {
+        warnings.push(format!(
+            "{} test file(s) were run but hidden from the JSON output, so they're not counted",
+            hidden
+        ));
+    }
+
+    let shards: BTreeSet<(usize, usize)> = summaries
+        .iter()
+        .filter_map(|summary| summary.shard)
+        .map(|shard| (shard.index, shard.total))
+        .collect();
+    let totals: BTreeSet<usize> = shards.iter().map(|(_, total)| *total).collect();
+    for total in totals {
+        let absent: Vec<String> = (1..=total)
+            .filter(|index| !shards.contains(&(*index, total)))
+            .map(|index| index.to_string())
+            .collect();
+        if !absent.is_empty() {
+            warnings.push(format!(
+                "Missing results of shard(s) {} of {}",
+                absent.join(", "),
+                total
+            ));
+        }
+    }
+
+    warnings
+}
+
+#[cfg(test)]
+mod merge_tests {
+    use assert2::{check, let_assert};
+
+    use super::*;
+
+    fn output(lines: &[&str]) -> String {
+        lines.join("\n")
+    }
+
+    #[test]
+    fn it_merges_reports_of_shards() {
+        let first = output(&[
+            r#"{"status":"completed","file":"a.nix","elapsed":10,"tests":[{"success":true,"path":["test"],"location":"a.nix:1","checks":[]}]}"#,
+            r#"{"status":"errored","file":"b.nix","error":"boom","elapsed":20}"#,
+            r#"{"status":"summary","shard":{"index":1,"total":2},"elapsed":40,"files":["a.nix","b.nix"]}"#,
+        ]);
+        let second = output(&[
+            r#"{"version":1,"event":"test_file_not_found","file":"missing.nix"}"#,
+            r#"{"version":1,"event":"test_file_completed","status":"timed_out","file":"c.nix","timeout":1,"elapsed":1000}"#,
+            r#"{"status":"resource_exceeded","file":"d.nix","limit":"cpu_time","max":30,"elapsed":31000}"#,
+            r#"{"status":"summary","shard":{"index":2,"total":2},"elapsed":32000,"files":["c.nix","d.nix"]}"#,
+        ]);
+
+        let merged = merge(&[
+            ("first.json".to_string(), first),
+            ("second.json".to_string(), second),
+        ])
+        .unwrap();
+
+        check!(merged.warnings.is_empty());
+        check!(merged.report.succeeded_files() == 1);
+        check!(merged.report.errored_files() == 1);
+        check!(merged.report.timed_out_files() == 1);
+        check!(merged.report.resource_exceeded_files() == 1);
+        check!(merged.report.total_elapsed() == 32000);
+    }
+
+    #[test]
+    fn it_reads_concatenated_json() {
+        let output = r#"{"status":"errored","file":"a.nix","error":"boom","elapsed":20}{"status":"errored","file":"b.nix","error":"boom","elapsed":20}"#;
+
+        let merged = merge(&[("out.json".to_string(), output.to_string())]).unwrap();
+
+        check!(merged.report.errored_files() == 2);
+        check!(merged.report.total_elapsed() == 40);
+    }
+
+    #[test]
+    fn it_warns_about_missing_results() {
+        let output = output(&[
+            r#"{"status":"errored","file":"b.nix","error":"boom","elapsed":20}"#,
+            r#"{"status":"summary","shard":{"index":2,"total":3},"elapsed":40,"files":["a.nix","b.nix"]}"#,
+        ]);
+
+        let merged = merge(&[("out.json".to_string(), output)]).unwrap();
+
+        check!(
+            merged.warnings
+                == [
+                    "1 test file(s) were run but hidden from the JSON output, so they're not counted",
+                    "Missing results of shard(s) 1, 3 of 3",
+                ]
+        );
+    }
+
+    #[test]
+    fn it_rejects_newer_versions() {
+        let output = r#"{"version":2,"event":"test_file_invalid","file":"a.nix"}"#;
+
+        let_assert!(Err(error) = merge(&[("out.json".to_string(), output.to_string())]));
+        check!(error.to_string() == "Unsupported JSON output version 2 in out.json");
+    }
+
+    #[test]
+    fn it_rejects_invalid_output() {
+        let_assert!(
+            Err(error) = merge(&[(
+                "out.json".to_string(),
+                "{\"status\":\"unknown\"}".to_string()
+            )])
+        );
+        check!(error.to_string() == "Invalid test file report 1 in out.json");
+
+        let_assert!(Err(_) = merge(&[("empty.json".to_string(), String::new())]));
+    }
+}
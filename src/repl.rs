@@ -0,0 +1,230 @@
+use std::{process::Stdio, sync::Mutex};
+
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader, Lines},
+    process::{Child, ChildStdin, ChildStdout, Command},
+    sync::Semaphore,
+};
+
+use crate::runners::Traces;
+
+const PROMPT: &str = "nix-repl> ";
+/// Printed after every input, once the repl is done with it
+const DONE_MARKER: &str = "\"__nix_tests_done__\"";
+/// Prefixes the printed value of an evaluation that succeeded, so anything
+/// else printed before the done marker is an error
+const RESULT_MARKER: &str = "__nix_tests_result__";
+
+/// Persistent `nix repl` processes, at most `size` of them, each evaluating
+/// one expression at a time.
+pub struct ReplPool {
+    setup: Vec<String>,
+    idle: Mutex<Vec<Repl>>,
+    slots: Semaphore,
+}
+
+impl ReplPool {
+    pub fn new(setup: Vec<String>, size: usize) -> Self {
+        Self {
+            setup,
+            idle: Mutex::new(Vec::new()),
+            slots: Semaphore::new(size.max(1)),
+        }
+    }
+
+    /// Evaluates an expression that returns a string, collecting the messages
+    /// it traces as they are printed.
+    pub async fn evaluate(&self, expr: &str, traces: &Traces) -> Result<Vec<u8>, String> {
+        let _slot = self
+            .slots
+            .acquire()
+            .await
+            .map_err(|err| format!("Failed to schedule nix repl evaluation: {err}"))?;
+        let idle = self.idle.lock().unwrap().pop();
+
+        let mut repl = match idle {
+            Some(repl) => repl,
+            None => Repl::spawn(&self.setup)
+                .await
+                .map_err(|err| format!("Failed to execute nix repl: {}", err))?,
+        };
+
+        let lines = repl
+            .send(&format!("\"{RESULT_MARKER}\" + ({expr})"), traces)
+            .await
+            .map_err(|err| format!("Nix repl exited unexpectedly: {}", err))?;
+
+        self.idle.lock().unwrap().push(repl);
+
+        parse_output(&lines)
+    }
+}
+
+struct Repl {
+    _child: Child,
+    stdin: ChildStdin,
+    stdout: Lines<BufReader<ChildStdout>>,
+}
+
+impl Repl {
+    async fn spawn(setup: &[String]) -> std::io::Result<Self> {
+        let mut cmd = Command::new("sh");
+        cmd.args([
+            "-c",
+            "exec nix repl --extra-experimental-features nix-command 2>&1",
+        ])
+        .env("NO_COLOR", "1")
+        .env("TERM", "dumb")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .kill_on_drop(true);
+        let mut child = cmd.spawn()?;
+
+        let stdin = child.stdin.take().expect("stdin is piped");
+        let stdout = BufReader::new(child.stdout.take().expect("stdout is piped")).lines();
+
+        let mut repl = Self {
+            _child: child,
+            stdin,
+            stdout,
+        };
+        repl.send(&setup.join("\n"), &Traces::default()).await?;
+
+        Ok(repl)
+    }
+
+    async fn send(&mut self, input: &str, traces: &Traces) -> std::io::Result<Vec<String>> {
+        self.stdin
+            .write_all(format!("{}\n{}\n", input, DONE_MARKER).as_bytes())
+            .await?;
+        self.stdin.flush().await?;
+
+        let mut lines = Vec::new();
+
+        while let Some(line) = self.stdout.next_line().await? {
+            let line = strip_prompt(&line);
+            if line == DONE_MARKER {
+                return Ok(lines);
+            }
+            if let Some(message) = line.strip_prefix("trace: ") {
+                traces.push(message);
+            }
+            if !line.trim().is_empty() {
+                lines.push(line.to_string());
+            }
+        }
+
+        Err(std::io::Error::new(
+            std::io::ErrorKind::UnexpectedEof,
+            lines.join("\n"),
+        ))
+    }
+}
+
+fn strip_prompt(mut line: &str) -> &str {
+    while let Some(rest) = line.strip_prefix(PROMPT) {
+        line = rest;
+    }
+    line.trim_end()
+}
+
+fn parse_output(lines: &[String]) -> Result<Vec<u8>, String> {
+    let Some(value) = lines.iter().rev().find_map(|line| {
+        parse_string_literal(line)?
+            .strip_prefix(RESULT_MARKER)
+            .map(|value| value.as_bytes().to_vec())
+    }) else {
+        return Err(match lines {
+            [] => "Nix repl printed no result".to_string(),
+            lines => lines.join("\n"),
+        });
+    };
+    Ok(value)
+}
+
+fn parse_string_literal(line: &str) -> Option<String> {
+    let inner = line.strip_prefix('"')?.strip_suffix('"')?;
+
+    let mut value = String::with_capacity(inner.len());
+    let mut chars = inner.chars();
+
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            value.push(c);
+            continue;
+        }
+        match chars.next()? {
+            'n' => value.push('\n'),
+            'r' => value.push('\r'),
+            't' => value.push('\t'),
+            other => value.push(other),
+        }
+    }
+
+    Some(value)
+}
+
+#[cfg(test)]
+mod repl_output_tests {
+    use assert2::{check, let_assert};
+
+    use super::*;
+
+    fn lines(lines: &[&str]) -> Vec<String> {
+        lines.iter().map(|line| line.to_string()).collect()
+    }
+
+    #[test]
+    fn it_strips_prompts() {
+        check!(strip_prompt("nix-repl> nix-repl> \"ok\"  ") == "\"ok\"");
+        check!(strip_prompt("nix-repl> ") == "");
+    }
+
+    #[test]
+    fn it_parses_printed_strings() {
+        let output = parse_output(&lines(&[
+            r#""__nix_tests_result__[{\"path\":[\"a\"],\"x\":\"\${y}\\n\"}]""#,
+        ]));
+
+        let_assert!(Ok(json) = output);
+        check!(String::from_utf8(json).unwrap() == r#"[{"path":["a"],"x":"${y}\n"}]"#);
+    }
+
+    #[test]
+    fn it_uses_the_last_printed_result() {
+        let output = parse_output(&lines(&[
+            "trace: something",
+            r#""__nix_tests_result__first""#,
+            r#""__nix_tests_result__[]""#,
+        ]));
+
+        let_assert!(Ok(json) = output);
+        check!(json == b"[]");
+    }
+
+    #[test]
+    fn it_reports_evaluation_errors() {
+        let output = parse_output(&lines(&[
+            "error: undefined variable 'foo'",
+            "       at /tmp/my_test.nix:3:5:",
+        ]));
+
+        let_assert!(Err(error) = output);
+        check!(error == "error: undefined variable 'foo'\n       at /tmp/my_test.nix:3:5:");
+    }
+
+    #[test]
+    fn it_reports_output_without_a_result_as_an_error() {
+        let output = parse_output(&lines(&["trace: something", r#""[]""#]));
+
+        let_assert!(Err(error) = output);
+        check!(error == "trace: something\n\"[]\"");
+    }
+
+    #[test]
+    fn it_reports_missing_output() {
+        let_assert!(Err(error) = parse_output(&[]));
+        check!(error == "Nix repl printed no result");
+    }
+}
@@ -1,6 +1,11 @@
+use std::{fs::File, io::Write, path::PathBuf, sync::Mutex};
+
+use anyhow::Context;
+
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-use crate::reports::config::Format;
+use crate::{reports::config::Format, shard::Shard};
 
 pub mod config {
     use serde::{Deserialize, Serialize};
@@ -21,6 +26,19 @@ pub mod config {
 
         #[serde(default)]
         pub hide_errored: bool,
+
+        #[serde(default)]
+        pub stats: bool,
+
+        #[serde(default)]
+        pub slowest: usize,
+
+        #[serde(default)]
+        pub warn_slower_than: u64,
+
+        /// File to write the report to instead of stdout
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub output: Option<String>,
     }
 
     impl Config {
@@ -30,6 +48,7 @@ pub mod config {
                 TestFileReport::Completed(_) => self.hide_failed,
                 TestFileReport::Errored(_) => self.hide_errored,
                 TestFileReport::TimedOut(_) => self.hide_errored,
+                TestFileReport::ResourceExceeded(_) => self.hide_errored,
             }
         }
     }
@@ -37,9 +56,29 @@ pub mod config {
     #[derive(Default, Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
     #[serde(rename_all = "kebab-case")]
     pub enum Format {
-        #[default]
         Human,
         Json,
+        /// Only failures, as `file:line: message` lines for editors
+        Quickfix,
+        /// Human output with annotations and a job summary for GitHub Actions
+        Github,
+        /// `github` when running in GitHub Actions, `human` otherwise
+        #[default]
+        Auto,
+        /// A self-contained HTML page, written once the run completes
+        Html,
+        /// A Markdown summary of the run, for comments on pull requests
+        Markdown,
+    }
+
+    impl Format {
+        pub fn resolve(&self, github_actions: bool) -> Format {
+            match self {
+                Format::Auto if github_actions => Format::Github,
+                Format::Auto => Format::Human,
+                format => format.clone(),
+            }
+        }
     }
 }
 
@@ -47,74 +86,195 @@ pub mod config {
 pub struct TestSuiteReport {
     reports: Vec<TestFileReport>,
     elapsed: u128,
+    seed: Option<u32>,
+    shard: Option<Shard>,
 }
 
 impl TestSuiteReport {
     pub fn new(reports: Vec<TestFileReport>, elapsed: u128) -> Self {
-        Self { reports, elapsed }
+        Self {
+            reports,
+            elapsed,
+            seed: None,
+            shard: None,
+        }
+    }
+    /// Records the seed test files were shuffled with.
+    pub fn with_seed(self, seed: u32) -> Self {
+        Self {
+            seed: Some(seed),
+            ..self
+        }
+    }
+    /// Records the shard of the test files that were run.
+    pub fn with_shard(self, shard: Shard) -> Self {
+        Self {
+            shard: Some(shard),
+            ..self
+        }
+    }
+    pub fn reports(&self) -> &[TestFileReport] {
+        &self.reports
     }
-    fn processed_files(&self) -> usize {
+    pub fn processed_files(&self) -> usize {
         self.reports.len()
     }
-    fn succeeded_files(&self) -> usize {
+    pub fn succeeded_files(&self) -> usize {
         self.reports
             .iter()
             .filter(|report| match report {
                 TestFileReport::Completed(report) => report.failed_count() == 0,
                 TestFileReport::Errored(_) => false,
                 TestFileReport::TimedOut(_) => false,
+                TestFileReport::ResourceExceeded(_) => false,
             })
             .count()
     }
-    fn failed_files(&self) -> usize {
+    pub fn failed_files(&self) -> usize {
         self.reports
             .iter()
             .filter(|report| match report {
                 TestFileReport::Completed(report) => report.failed_count() > 0,
                 TestFileReport::Errored(_) => false,
                 TestFileReport::TimedOut(_) => false,
+                TestFileReport::ResourceExceeded(_) => false,
             })
             .count()
     }
-    fn errored_files(&self) -> usize {
+    pub fn errored_files(&self) -> usize {
         self.reports
             .iter()
             .filter(|report| matches!(report, TestFileReport::Errored(_)))
             .count()
     }
-    fn timed_out_files(&self) -> usize {
+    pub fn timed_out_files(&self) -> usize {
         self.reports
             .iter()
             .filter(|report| matches!(report, TestFileReport::TimedOut(_)))
             .count()
     }
-    fn total_elapsed(&self) -> u128 {
+    pub fn resource_exceeded_files(&self) -> usize {
+        self.reports
+            .iter()
+            .filter(|report| matches!(report, TestFileReport::ResourceExceeded(_)))
+            .count()
+    }
+    pub fn total_elapsed(&self) -> u128 {
         self.elapsed
     }
+    fn slowest_files(&self, count: usize) -> Vec<&TestFileReport> {
+        let mut reports: Vec<&TestFileReport> = self.reports.iter().collect();
+        reports.sort_by(|a, b| b.elapsed().cmp(&a.elapsed()).then(a.file().cmp(b.file())));
+        reports.truncate(count);
+        reports
+    }
+    fn slowest_tests(&self, count: usize) -> Vec<(&str, &TestReport, u128)> {
+        let mut tests: Vec<(&str, &TestReport, u128)> = self
+            .reports
+            .iter()
+            .filter_map(|report| match report {
+                TestFileReport::Completed(report) => Some(report),
+                _ => None,
+            })
+            .flat_map(|report| {
+                report
+                    .tests
+                    .iter()
+                    .filter_map(|test| Some((report.file.as_str(), test, test.elapsed?)))
+            })
+            .collect();
+        tests.sort_by_key(|test| std::cmp::Reverse(test.2));
+        tests.truncate(count);
+        tests
+    }
+    fn slower_than(&self, budget: u64) -> usize {
+        self.reports
+            .iter()
+            .filter(|report| report.elapsed() > budget as u128)
+            .count()
+    }
+    /// Sums CPU times and Nix counters, and takes the largest peak memory and
+    /// GC heap, over the files that have resource usage.
+    fn total_stats(&self) -> Option<ResourceStats> {
+        self.reports
+            .iter()
+            .filter_map(TestFileReport::stats)
+            .fold(None, |total, stats| {
+                Some(total.unwrap_or_default().add(stats))
+            })
+    }
+    /// Counts the files evaluated without resource usage: cached results,
+    /// batched files and files evaluated by the repl.
+    fn unmeasured_files(&self) -> usize {
+        self.reports
+            .iter()
+            .filter(|report| {
+                !matches!(report, TestFileReport::TimedOut(_)) && report.stats().is_none()
+            })
+            .count()
+    }
     pub fn has_issues(&self) -> bool {
         self.reports.iter().any(|report| match report {
             TestFileReport::Completed(report) => report.failed_count() > 0,
             TestFileReport::Errored(_) => true,
             TestFileReport::TimedOut(_) => true,
+            TestFileReport::ResourceExceeded(_) => true,
         })
     }
 }
 
-#[derive(Debug, Serialize, PartialEq, Eq, Clone)]
+#[derive(Debug, Serialize, PartialEq, Eq, Clone, JsonSchema)]
 #[serde(tag = "status", rename_all = "snake_case")]
 pub enum TestFileReport {
     Completed(TestFileCompletedReport),
     Errored(TestFileErroredReport),
     TimedOut(TestFileTimedOutReport),
+    ResourceExceeded(TestFileResourceExceededReport),
+}
+
+impl<'de> Deserialize<'de> for TestFileReport {
+    // The derived implementation buffers internally tagged content, which
+    // can't hold the u128 elapsed times, so dispatch on the status by hand
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        use serde::de::Error;
+
+        let value = serde_json::Value::deserialize(deserializer)?;
+        let status = value
+            .get("status")
+            .and_then(serde_json::Value::as_str)
+            .ok_or_else(|| D::Error::missing_field("status"))?;
+
+        match status {
+            "completed" => serde_json::from_value(value).map(TestFileReport::Completed),
+            "errored" => serde_json::from_value(value).map(TestFileReport::Errored),
+            "timed_out" => serde_json::from_value(value).map(TestFileReport::TimedOut),
+            "resource_exceeded" => {
+                serde_json::from_value(value).map(TestFileReport::ResourceExceeded)
+            }
+            status => {
+                return Err(D::Error::unknown_variant(
+                    status,
+                    &["completed", "errored", "timed_out", "resource_exceeded"],
+                ))
+            }
+        }
+        .map_err(D::Error::custom)
+    }
 }
 
-#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone, JsonSchema)]
 pub struct TestFileCompletedReport {
     pub tests: Vec<TestReport>,
-    #[serde(skip_deserializing, default)]
+    #[serde(default)]
     pub file: String,
-    #[serde(skip_deserializing, default)]
+    #[serde(default)]
     pub elapsed: u128,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub mocks: Vec<String>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub cached: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stats: Option<ResourceStats>,
 }
 
 impl TestFileCompletedReport {
@@ -126,37 +286,216 @@ impl TestFileCompletedReport {
     }
 }
 
-#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, JsonSchema)]
 pub struct TestReport {
     pub success: bool,
     pub path: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub case: Option<String>,
     pub location: String,
     pub checks: Vec<CheckReport>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub elapsed: Option<u128>,
+}
+
+/// A test found by listing a test file, whose checks haven't been run.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct TestListing {
+    pub path: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub case: Option<String>,
+    pub location: String,
+    pub checks: Vec<CheckListing>,
 }
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct CheckListing {
+    pub name: String,
+    pub location: String,
+}
+
+/// A test file found by `nix-tests list`, with its tests when they were
+/// listed or the error that kept them from being listed.
+#[derive(Serialize, Debug, PartialEq, Eq, Clone)]
+pub struct TestFileListing {
+    pub file: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tests: Option<Vec<TestListing>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl TestFileListing {
+    pub fn human(&self) -> String {
+        let mut output = format!("{}\n", self.file);
+        for test in self.tests.iter().flatten() {
+            output.push_str(&format!(
+                "  {} ({})\n",
+                test.path.join(" -> "),
+                test.location
+            ));
+            for check in &test.checks {
+                output.push_str(&format!("    - {} ({})\n", check.name, check.location));
+            }
+        }
+        output
+    }
+
+    pub fn json(&self) -> String {
+        format!("{}\n", serde_json::to_string(self).unwrap())
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, JsonSchema)]
 pub struct CheckReport {
     pub name: String,
     pub success: bool,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub failure: Option<String>,
     pub location: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub property: Option<PropertyReport>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub build: Option<BuildReport>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub definitions: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, JsonSchema)]
+pub struct PropertyReport {
+    pub seed: u32,
+    pub iterations: u64,
+    pub shrinks: u64,
+    pub input: serde_json::Value,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, JsonSchema)]
+pub struct BuildReport {
+    pub drv_path: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output_contains: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output: Option<String>,
 }
 
-#[derive(Debug, Serialize, PartialEq, Eq, Clone)]
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone, JsonSchema)]
 pub struct TestFileErroredReport {
     pub file: String,
     pub error: String,
     pub elapsed: u128,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stats: Option<ResourceStats>,
 }
 
-#[derive(Debug, Serialize, PartialEq, Eq, Clone)]
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone, JsonSchema)]
 pub struct TestFileTimedOutReport {
     pub file: String,
     pub timeout: u64,
     pub elapsed: u128,
 }
 
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone, JsonSchema)]
+pub struct TestFileResourceExceededReport {
+    pub file: String,
+    #[serde(flatten)]
+    pub limit: ResourceLimit,
+    pub elapsed: u128,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stats: Option<ResourceStats>,
+}
+
+/// Resources used by the Nix process that evaluated a test file.
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq, Eq, Clone, JsonSchema)]
+pub struct ResourceStats {
+    /// Peak resident set size in bytes
+    pub peak_memory: u64,
+    /// User CPU time in milliseconds
+    pub user_time: u64,
+    /// System CPU time in milliseconds
+    pub system_time: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nix: Option<NixStats>,
+}
+
+/// Evaluator statistics reported by Nix through `NIX_SHOW_STATS`.
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq, Eq, Clone, JsonSchema)]
+pub struct NixStats {
+    pub thunks: u64,
+    pub function_calls: u64,
+    pub primop_calls: u64,
+    /// GC heap size in bytes
+    pub gc_heap_size: u64,
+}
+
+impl ResourceStats {
+    fn add(mut self, other: &ResourceStats) -> Self {
+        self.peak_memory = self.peak_memory.max(other.peak_memory);
+        self.user_time += other.user_time;
+        self.system_time += other.system_time;
+        if let Some(other) = &other.nix {
+            let nix = self.nix.get_or_insert_with(NixStats::default);
+            nix.thunks += other.thunks;
+            nix.function_calls += other.function_calls;
+            nix.primop_calls += other.primop_calls;
+            nix.gc_heap_size = nix.gc_heap_size.max(other.gc_heap_size);
+        }
+        self
+    }
+}
+
+impl TestFileReport {
+    pub fn file(&self) -> &str {
+        match self {
+            TestFileReport::Completed(report) => &report.file,
+            TestFileReport::Errored(report) => &report.file,
+            TestFileReport::TimedOut(report) => &report.file,
+            TestFileReport::ResourceExceeded(report) => &report.file,
+        }
+    }
+
+    pub fn elapsed(&self) -> u128 {
+        match self {
+            TestFileReport::Completed(report) => report.elapsed,
+            TestFileReport::Errored(report) => report.elapsed,
+            TestFileReport::TimedOut(report) => report.elapsed,
+            TestFileReport::ResourceExceeded(report) => report.elapsed,
+        }
+    }
+
+    fn stats(&self) -> Option<&ResourceStats> {
+        match self {
+            TestFileReport::Completed(report) => report.stats.as_ref(),
+            TestFileReport::Errored(report) => report.stats.as_ref(),
+            TestFileReport::TimedOut(_) => None,
+            TestFileReport::ResourceExceeded(report) => report.stats.as_ref(),
+        }
+    }
+}
+
+fn format_bytes(bytes: u64) -> String {
+    format!("{:.1}MiB", bytes as f64 / (1024.0 * 1024.0))
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone, Copy, JsonSchema)]
+#[serde(tag = "limit", content = "max", rename_all = "snake_case")]
+pub enum ResourceLimit {
+    /// Address space in MiB
+    Memory(u64),
+    /// CPU time in seconds
+    CpuTime(u64),
+    OpenFiles(u64),
+}
+
+impl std::fmt::Display for ResourceLimit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ResourceLimit::Memory(max) => write!(f, "{}MiB memory", max),
+            ResourceLimit::CpuTime(max) => write!(f, "{}s CPU time", max),
+            ResourceLimit::OpenFiles(max) => write!(f, "{} open files", max),
+        }
+    }
+}
+
 #[derive(Debug, PartialEq, Eq, Clone)]
 #[allow(clippy::enum_variant_names)]
 pub enum ReportEvent {
@@ -169,6 +508,18 @@ pub enum ReportEvent {
 #[cfg_attr(test, mockall::automock)]
 pub trait Reporter {
     fn on(&self, report_event: &ReportEvent) -> Option<String>;
+
+    /// Output that goes to stdout even when the report is written to a file.
+    fn on_stdout(&self, _report_event: &ReportEvent) -> Option<String> {
+        None
+    }
+}
+
+fn concat(first: Option<String>, second: Option<String>) -> Option<String> {
+    match (first, second.filter(|second| !second.is_empty())) {
+        (Some(first), Some(second)) => Some(first + &second),
+        (first, second) => first.or(second),
+    }
 }
 
 pub struct JsonReporter {
@@ -181,17 +532,98 @@ impl JsonReporter {
     }
 }
 
+/// Version of the JSON output, raised on changes that aren't backwards
+/// compatible.
+pub const JSON_VERSION: u32 = 1;
+
+/// A line of the JSON output. Every report event is written as one, tagged
+/// with `event`.
+#[derive(Debug, Serialize, JsonSchema)]
+#[schemars(title = "nix-tests JSON event")]
+pub struct JsonEvent<'a> {
+    pub version: u32,
+    #[serde(flatten)]
+    pub event: JsonEventKind<'a>,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+#[serde(tag = "event", rename_all = "snake_case")]
+#[allow(clippy::enum_variant_names)]
+pub enum JsonEventKind<'a> {
+    TestFileNotFound { file: &'a str },
+    TestFileInvalid { file: &'a str },
+    TestFileCompleted(&'a TestFileReport),
+    TestSuiteCompleted(JsonSummary),
+}
+
+/// Last line of the JSON output, describing the run as a whole so the
+/// results of several shards can be merged.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone, JsonSchema)]
+#[serde(tag = "status", rename = "summary")]
+#[schemars(rename = "JsonSummary")]
+pub struct JsonSummary {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub shard: Option<Shard>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seed: Option<u32>,
+    pub elapsed: u128,
+    #[serde(default)]
+    pub counts: JsonCounts,
+    pub files: Vec<String>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, PartialEq, Eq, Clone, JsonSchema)]
+pub struct JsonCounts {
+    pub succeeded: usize,
+    pub failed: usize,
+    pub errored: usize,
+    pub timed_out: usize,
+    pub resource_exceeded: usize,
+}
+
+/// Returns the JSON Schema of a line of the JSON output.
+pub fn json_schema() -> String {
+    let schema = schemars::schema_for!(JsonEvent<'static>);
+    serde_json::to_string_pretty(&schema).unwrap() + "\n"
+}
+
 impl Reporter for JsonReporter {
     fn on(&self, report_event: &ReportEvent) -> Option<String> {
-        if let ReportEvent::TestFileCompleted(report) = report_event {
-            if self.config.should_hide_test_report(report) {
-                None
-            } else {
-                Some(serde_json::to_string(report).unwrap())
+        let event = match report_event {
+            ReportEvent::TestFileNotFound(file) => JsonEventKind::TestFileNotFound { file },
+            ReportEvent::TestFileInvalid(file) => JsonEventKind::TestFileInvalid { file },
+            ReportEvent::TestFileCompleted(report) => {
+                if self.config.should_hide_test_report(report) {
+                    return None;
+                }
+                JsonEventKind::TestFileCompleted(report)
             }
-        } else {
-            None
-        }
+            ReportEvent::TestSuiteCompleted(report) => {
+                JsonEventKind::TestSuiteCompleted(JsonSummary {
+                    shard: report.shard,
+                    seed: report.seed,
+                    elapsed: report.elapsed,
+                    counts: JsonCounts {
+                        succeeded: report.succeeded_files(),
+                        failed: report.failed_files(),
+                        errored: report.errored_files(),
+                        timed_out: report.timed_out_files(),
+                        resource_exceeded: report.resource_exceeded_files(),
+                    },
+                    files: report
+                        .reports
+                        .iter()
+                        .map(|report| report.file().to_string())
+                        .collect(),
+                })
+            }
+        };
+
+        let event = JsonEvent {
+            version: JSON_VERSION,
+            event,
+        };
+        Some(serde_json::to_string(&event).unwrap() + "\n")
     }
 }
 
@@ -209,25 +641,32 @@ impl HumanReporter {
 
         match result {
             TestFileReport::Completed(report) => {
-                output.push_str(&format!("File: {} ({}ms)\n", report.file, report.elapsed));
+                if report.cached {
+                    output.push_str(&format!(
+                        "File: {} (cached, {}ms)\n",
+                        report.file, report.elapsed
+                    ));
+                } else {
+                    output.push_str(&format!("File: {} ({}ms)\n", report.file, report.elapsed));
+                }
+                self.format_stats(&mut output, result);
+                self.format_budget(&mut output, result);
 
-                for test in &report.tests {
-                    let path = test.path.join(" -> ");
+                let mut group: Option<&[String]> = None;
 
-                    for check in &test.checks {
-                        if check.success {
-                            output.push_str(&format!("✓ {} -> {}\n", path, check.name));
-                        } else {
-                            output.push_str(&format!("✗ {} -> {}\n", path, check.name));
-                            if let Some(failure) = &check.failure {
-                                output.push_str("    Failure:\n");
-                                for line in failure.lines() {
-                                    output.push_str(&format!("      {}\n", line));
-                                }
-                                output.push_str(&format!("      at {}\n", check.location));
-                            } else {
-                                output.push_str(&format!("    Failed at {}\n", check.location));
+                for test in &report.tests {
+                    match &test.case {
+                        Some(case) => {
+                            let parent = &test.path[..test.path.len().saturating_sub(1)];
+                            if group != Some(parent) {
+                                output.push_str(&format!("{}\n", parent.join(" -> ")));
+                                group = Some(parent);
                             }
+                            self.format_checks(&mut output, test, case, "  ");
+                        }
+                        None => {
+                            group = None;
+                            self.format_checks(&mut output, test, &test.path.join(" -> "), "");
                         }
                     }
                 }
@@ -239,6 +678,8 @@ impl HumanReporter {
             }
             TestFileReport::Errored(report) => {
                 output.push_str(&format!("File: {} ({}ms)\n", report.file, report.elapsed));
+                self.format_stats(&mut output, result);
+                self.format_budget(&mut output, result);
                 output.push_str(&format!("ERROR: {}\n", report.error));
             }
             TestFileReport::TimedOut(report) => {
@@ -246,10 +687,142 @@ impl HumanReporter {
                 output.push_str(&format!("TIMEOUT: Exceeded {}ms limit\n", report.timeout));
                 output.push('\n');
             }
+            TestFileReport::ResourceExceeded(report) => {
+                output.push_str(&format!("File: {} ({}ms)\n", report.file, report.elapsed));
+                self.format_stats(&mut output, result);
+                output.push_str(&format!(
+                    "RESOURCE EXCEEDED: Exceeded {} limit\n",
+                    report.limit
+                ));
+                output.push('\n');
+            }
         }
 
         output
     }
+
+    fn format_stats(&self, output: &mut String, result: &TestFileReport) {
+        if !self.config.stats {
+            return;
+        }
+        let Some(stats) = result.stats() else {
+            output.push_str("Stats: not measured\n");
+            return;
+        };
+
+        output.push_str(&format!(
+            "Stats: {} peak memory, {}ms user, {}ms system\n",
+            format_bytes(stats.peak_memory),
+            stats.user_time,
+            stats.system_time
+        ));
+        if let Some(nix) = &stats.nix {
+            output.push_str(&format!(
+                "Nix: {} thunks, {} function calls, {} primop calls, {} GC heap\n",
+                nix.thunks,
+                nix.function_calls,
+                nix.primop_calls,
+                format_bytes(nix.gc_heap_size)
+            ));
+        }
+    }
+
+    fn format_budget(&self, output: &mut String, result: &TestFileReport) {
+        let budget = self.config.warn_slower_than;
+        if budget > 0 && result.elapsed() > budget as u128 {
+            output.push_str(&format!("WARNING: Slower than {}ms\n", budget));
+        }
+    }
+
+    fn format_checks(&self, output: &mut String, test: &TestReport, path: &str, indent: &str) {
+        for check in &test.checks {
+            if check.success {
+                output.push_str(&format!("{indent}✓ {} -> {}\n", path, check.name));
+            } else {
+                output.push_str(&format!("{indent}✗ {} -> {}\n", path, check.name));
+                if let Some(failure) = &check.failure {
+                    output.push_str(&format!("{indent}    Failure:\n"));
+                    for line in failure.lines() {
+                        output.push_str(&format!("{indent}      {}\n", line));
+                    }
+                    if let Some(property) = &check.property {
+                        output.push_str(&format!("{indent}      Input: {}\n", property.input));
+                        output.push_str(&format!(
+                            "{indent}      Seed: {} ({} iteration(s), {} shrink(s))\n",
+                            property.seed, property.iterations, property.shrinks
+                        ));
+                    }
+                    if !check.definitions.is_empty() {
+                        output.push_str(&format!("{indent}      Defined in:\n"));
+                        for definition in &check.definitions {
+                            output.push_str(&format!("{indent}        {}\n", definition));
+                        }
+                    }
+                    output.push_str(&format!("{indent}      at {}\n", check.location));
+                } else {
+                    output.push_str(&format!("{indent}    Failed at {}\n", check.location));
+                }
+            }
+        }
+    }
+
+    fn format_timings(&self, output: &mut String, report: &TestSuiteReport) {
+        let budget = self.config.warn_slower_than;
+        if budget > 0 && report.slower_than(budget) > 0 {
+            output.push_str(&format!(
+                "Warning: {} file(s) slower than {}ms\n",
+                report.slower_than(budget),
+                budget
+            ));
+        }
+
+        let slowest = report.slowest_files(self.config.slowest);
+        if !slowest.is_empty() {
+            output.push_str("Slowest files:\n");
+            for file in slowest {
+                output.push_str(&format!("  {}ms {}\n", file.elapsed(), file.file()));
+            }
+        }
+
+        let slowest = report.slowest_tests(self.config.slowest);
+        if !slowest.is_empty() {
+            output.push_str("Slowest tests:\n");
+            for (file, test, elapsed) in slowest {
+                output.push_str(&format!(
+                    "  {}ms {}: {}\n",
+                    elapsed,
+                    file,
+                    test.path.join(" -> ")
+                ));
+            }
+        }
+    }
+
+    fn format_total_stats(&self, output: &mut String, report: &TestSuiteReport) {
+        if !self.config.stats {
+            return;
+        }
+        if let Some(stats) = report.total_stats() {
+            output.push_str(&format!(
+                "Peak memory: {}\nCPU time: {}ms user, {}ms system\n",
+                format_bytes(stats.peak_memory),
+                stats.user_time,
+                stats.system_time
+            ));
+            if let Some(nix) = &stats.nix {
+                output.push_str(&format!(
+                    "Nix: {} thunks, {} function calls, {} primop calls\n",
+                    nix.thunks, nix.function_calls, nix.primop_calls
+                ));
+            }
+        }
+        if report.unmeasured_files() > 0 {
+            output.push_str(&format!(
+                "Stats not measured for {} file(s)\n",
+                report.unmeasured_files()
+            ));
+        }
+    }
 }
 
 impl Reporter for HumanReporter {
@@ -276,11 +849,13 @@ impl Reporter for HumanReporter {
                 } else if report.failed_files() == 0
                     && report.errored_files() == 0
                     && report.timed_out_files() == 0
+                    && report.resource_exceeded_files() == 0
                 {
                     output.push_str(&format!(
                         "All tests passed ({}ms)\n",
                         report.total_elapsed()
                     ));
+                    self.format_total_stats(&mut output, report);
                 } else {
                     output.push_str(&format!("{} file(s) succeeded\n", report.succeeded_files()));
                     if report.errored_files() > 0 {
@@ -294,74 +869,710 @@ impl Reporter for HumanReporter {
                         output
                             .push_str(&format!("{} file(s) timed out\n", report.timed_out_files()));
                     }
+                    if report.resource_exceeded_files() > 0 {
+                        output.push_str(&format!(
+                            "{} file(s) exceeded resource limits\n",
+                            report.resource_exceeded_files()
+                        ));
+                    }
+                    self.format_total_stats(&mut output, report);
                     output.push_str(&format!("Total time: {}ms\n", report.total_elapsed()));
                 }
 
+                if let Some(seed) = report.seed {
+                    output.push_str(&format!("Seed: {}\n", seed));
+                }
+
+                self.format_timings(&mut output, report);
+
                 Some(output)
             }
         }
     }
 }
 
-pub struct ConfigurableReporter {
-    inner: Box<dyn Reporter + Send + Sync>,
+/// Reports failures as `file:line: message` lines, which Vim's `:make` and
+/// Emacs' compilation mode can jump to.
+pub struct QuickfixReporter {
+    config: config::Config,
 }
 
-impl ConfigurableReporter {
-    pub fn new(report_config: &config::Config) -> Self {
-        let inner: Box<dyn Reporter + Send + Sync> = match report_config.format {
-            Format::Human => Box::new(HumanReporter::new(report_config.clone())),
-            Format::Json => Box::new(JsonReporter::new(report_config.clone())),
+impl QuickfixReporter {
+    pub fn new(config: config::Config) -> Self {
+        Self { config }
+    }
+
+    fn format_report(&self, report: &TestFileReport) -> String {
+        let mut output = String::new();
+        let mut line = |location: &str, message: &str| {
+            // Every failure must stay on its own line to be parsed
+            let message = message
+                .lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty())
+                .collect::<Vec<_>>()
+                .join("; ");
+            output.push_str(&format!("{}: {}\n", location, message));
         };
-        Self { inner }
+
+        match report {
+            TestFileReport::Completed(report) => {
+                for test in &report.tests {
+                    for check in test.checks.iter().filter(|check| !check.success) {
+                        line(
+                            &check.location,
+                            &format!(
+                                "{} -> {}: {}",
+                                test.path.join(" -> "),
+                                check.name,
+                                check.failure.as_deref().unwrap_or("Check failed")
+                            ),
+                        );
+                    }
+                }
+            }
+            TestFileReport::Errored(report) => line(
+                &error_location(&report.error).unwrap_or(format!("{}:1", report.file)),
+                error_message(&report.error),
+            ),
+            TestFileReport::TimedOut(report) => line(
+                &format!("{}:1", report.file),
+                &format!("Timed out after exceeding {}ms limit", report.timeout),
+            ),
+            TestFileReport::ResourceExceeded(report) => line(
+                &format!("{}:1", report.file),
+                &format!("Exceeded {} limit", report.limit),
+            ),
+        }
+
+        output
     }
 }
 
-impl Reporter for ConfigurableReporter {
+/// The innermost `at FILE:LINE:COLUMN:` location of a Nix error, as
+/// `FILE:LINE`.
+fn error_location(error: &str) -> Option<String> {
+    error.lines().rev().find_map(|line| {
+        let position = line.trim().strip_prefix("at ")?.strip_suffix(':')?;
+        let mut parts = position.rsplitn(3, ':');
+        let (_column, line, file) = (
+            parts.next()?.parse::<u32>().ok()?,
+            parts.next()?.parse::<u32>().ok()?,
+            parts.next()?,
+        );
+        Some(format!("{}:{}", file, line))
+    })
+}
+
+/// The last `error:` line of a Nix error, which tells what went wrong
+/// without the trace leading to it.
+fn error_message(error: &str) -> &str {
+    error
+        .lines()
+        .rev()
+        .map(str::trim)
+        .find_map(|line| line.strip_prefix("error:"))
+        .map(str::trim)
+        .filter(|message| !message.is_empty())
+        .unwrap_or(error.trim())
+}
+
+impl Reporter for QuickfixReporter {
     fn on(&self, report_event: &ReportEvent) -> Option<String> {
-        self.inner.on(report_event)
+        match report_event {
+            ReportEvent::TestFileCompleted(report)
+                if !self.config.should_hide_test_report(report) =>
+            {
+                Some(self.format_report(report)).filter(|output| !output.is_empty())
+            }
+            _ => None,
+        }
     }
 }
 
-#[cfg(test)]
-mod test_suite_report_tests {
-    use assert2::check;
-
-    use super::test_helpers::*;
+/// Adds workflow commands annotating failures to the human output, and
+/// writes a summary of the run for the job page in GitHub Actions.
+pub struct GithubReporter {
+    human: HumanReporter,
+    /// Annotated files must be relative to the repository
+    workspace: Option<PathBuf>,
+    step_summary: Option<PathBuf>,
+}
 
-    #[test]
-    fn it_counts_files() {
-        let report = test_suite_report(
-            vec![
-                completed_test_file("file1.nix", 0, vec![]),
-                completed_test_file("file2.nix", 0, vec![]),
-                errored_test_file("file3.nix", "error", 0),
-                errored_test_file("file4.nix", "error", 0),
-                errored_test_file("file5.nix", "error", 0),
-                completed_test_file(
-                    "file6.nix",
-                    0,
-                    vec![failed_test_report(
-                        vec!["test"],
-                        "file:1",
-                        vec![failed_check_report_with_message("check", "failed")],
-                    )],
-                ),
-            ],
-            0,
-        );
+impl GithubReporter {
+    pub fn new(
+        config: config::Config,
+        workspace: Option<PathBuf>,
+        step_summary: Option<PathBuf>,
+    ) -> Self {
+        Self {
+            human: HumanReporter::new(config),
+            workspace,
+            step_summary,
+        }
+    }
 
-        check!(report.processed_files() == 6);
-        check!(report.succeeded_files() == 2);
-        check!(report.failed_files() == 1);
-        check!(report.errored_files() == 3);
+    /// Takes the workspace and summary file from the environment of a job.
+    pub fn from_env(config: config::Config) -> Self {
+        let var = |name| {
+            std::env::var_os(name)
+                .filter(|value| !value.is_empty())
+                .map(PathBuf::from)
+        };
+        Self::new(
+            config,
+            var("GITHUB_WORKSPACE").or_else(|| std::env::current_dir().ok()),
+            var("GITHUB_STEP_SUMMARY"),
+        )
     }
 
-    #[test]
-    fn it_handles_empty_report() {
-        let report = test_suite_report(vec![], 0);
+    fn annotations(&self, report: &TestFileReport) -> String {
+        let mut output = String::new();
+        let mut error = |location: &str, title: &str, message: &str| {
+            let (file, line) = match location.rsplit_once(':') {
+                Some((file, line)) if line.parse::<u32>().is_ok() => (file, line),
+                _ => (location, "1"),
+            };
+            output.push_str(&format!(
+                "::error file={},line={},title={}::{}\n",
+                escape_property(&self.relative(file)),
+                line,
+                escape_property(title),
+                escape_data(message)
+            ));
+        };
 
-        check!(report.processed_files() == 0);
+        match report {
+            TestFileReport::Completed(report) => {
+                for test in &report.tests {
+                    for check in test.checks.iter().filter(|check| !check.success) {
+                        error(
+                            &check.location,
+                            &format!("{} -> {}", test.path.join(" -> "), check.name),
+                            check.failure.as_deref().unwrap_or("Check failed"),
+                        );
+                    }
+                }
+            }
+            TestFileReport::Errored(report) => error(
+                &error_location(&report.error).unwrap_or(report.file.clone()),
+                "Evaluation error",
+                &report.error,
+            ),
+            TestFileReport::TimedOut(report) => error(
+                &report.file,
+                "Timed out",
+                &format!("Exceeded {}ms limit", report.timeout),
+            ),
+            TestFileReport::ResourceExceeded(report) => error(
+                &report.file,
+                "Resource exceeded",
+                &format!("Exceeded {} limit", report.limit),
+            ),
+        }
+
+        output
+    }
+
+    fn relative(&self, file: &str) -> String {
+        let path = std::path::Path::new(file);
+        let path = self
+            .workspace
+            .as_deref()
+            .and_then(|workspace| path.strip_prefix(workspace).ok())
+            .unwrap_or(path);
+        let path = path.strip_prefix("./").unwrap_or(path);
+        path.display().to_string()
+    }
+
+    fn write_step_summary(&self, report: &TestSuiteReport) -> Option<String> {
+        let path = self.step_summary.as_ref()?;
+        // Other steps of the job write to the same file
+        let written = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .and_then(|mut file| {
+                file.write_all(markdown_summary(report, &self.human.config).as_bytes())
+            });
+
+        written.err().map(|err| {
+            format!(
+                "Warning: Failed to write job summary to {}: {}\n",
+                path.display(),
+                err
+            )
+        })
+    }
+}
+
+fn escape_data(value: &str) -> String {
+    value
+        .replace('%', "%25")
+        .replace('\r', "%0D")
+        .replace('\n', "%0A")
+}
+
+fn escape_property(value: &str) -> String {
+    escape_data(value).replace(':', "%3A").replace(',', "%2C")
+}
+
+impl Reporter for GithubReporter {
+    fn on(&self, report_event: &ReportEvent) -> Option<String> {
+        self.human.on(report_event)
+    }
+
+    /// Workflow commands are only picked up from the log of the job.
+    fn on_stdout(&self, report_event: &ReportEvent) -> Option<String> {
+        match report_event {
+            // Annotations are added even for hidden reports, as they're shown apart from the log
+            ReportEvent::TestFileCompleted(report) => Some(self.annotations(report)),
+            ReportEvent::TestSuiteCompleted(report) => self.write_step_summary(report),
+            _ => None,
+        }
+        .filter(|output| !output.is_empty())
+    }
+}
+
+/// Summarises a run as Markdown, with the message of every failure of the
+/// reports that aren't hidden and the slowest files and tests, for job
+/// summaries and comments on pull requests.
+pub fn markdown_summary(report: &TestSuiteReport, config: &config::Config) -> String {
+    let mut output = String::new();
+
+    let passed = report.failed_files() == 0
+        && report.errored_files() == 0
+        && report.timed_out_files() == 0
+        && report.resource_exceeded_files() == 0;
+    output.push_str(&format!(
+        "## nix-tests: {}\n\n",
+        if report.processed_files() == 0 {
+            "No test files found"
+        } else if passed {
+            "All tests passed"
+        } else {
+            "Tests failed"
+        }
+    ));
+
+    output.push_str("| Succeeded | Failed | Errored | Timed out | Resource exceeded | Time |\n");
+    output.push_str("| --- | --- | --- | --- | --- | --- |\n");
+    output.push_str(&format!(
+        "| {} | {} | {} | {} | {} | {}ms |\n",
+        report.succeeded_files(),
+        report.failed_files(),
+        report.errored_files(),
+        report.timed_out_files(),
+        report.resource_exceeded_files(),
+        report.total_elapsed()
+    ));
+
+    if let Some(shard) = &report.shard {
+        output.push_str(&format!("\nShard {} of {}\n", shard.index, shard.total));
+    }
+    if let Some(seed) = report.seed {
+        output.push_str(&format!("\nSeed: {}\n", seed));
+    }
+
+    let mut failures = String::new();
+    for file_report in report
+        .reports
+        .iter()
+        .filter(|file_report| !config.should_hide_test_report(file_report))
+    {
+        match file_report {
+            TestFileReport::Completed(file) => {
+                for test in &file.tests {
+                    for check in test.checks.iter().filter(|check| !check.success) {
+                        failures.push_str(&format!(
+                            "- **{} -> {}** at `{}`\n",
+                            test.path.join(" -> "),
+                            check.name,
+                            check.location
+                        ));
+                        if let Some(failure) = &check.failure {
+                            failures.push_str(&code_block(failure));
+                        }
+                    }
+                }
+            }
+            TestFileReport::Errored(file) => {
+                failures.push_str(&format!("- **Error** in `{}`\n", file.file));
+                failures.push_str(&code_block(&file.error));
+            }
+            TestFileReport::TimedOut(file) => {
+                failures.push_str(&format!(
+                    "- **Timed out** `{}` after exceeding {}ms limit\n",
+                    file.file, file.timeout
+                ));
+            }
+            TestFileReport::ResourceExceeded(file) => {
+                failures.push_str(&format!(
+                    "- **Resource exceeded** `{}`: exceeded {} limit\n",
+                    file.file, file.limit
+                ));
+            }
+        }
+    }
+    if !failures.is_empty() {
+        output.push_str("\n### Failures\n\n");
+        output.push_str(&failures);
+    }
+
+    let slowest = report.slowest_files(config.slowest);
+    if !slowest.is_empty() {
+        output.push_str("\n### Slowest files\n\n");
+        for file in slowest {
+            output.push_str(&format!("- {}ms `{}`\n", file.elapsed(), file.file()));
+        }
+    }
+
+    let slowest = report.slowest_tests(config.slowest);
+    if !slowest.is_empty() {
+        output.push_str("\n### Slowest tests\n\n");
+        for (file, test, elapsed) in slowest {
+            output.push_str(&format!(
+                "- {}ms `{}`: {}\n",
+                elapsed,
+                file,
+                test.path.join(" -> ")
+            ));
+        }
+    }
+
+    output
+}
+
+/// Fences a message inside a list item, so it's shown as written.
+fn code_block(message: &str) -> String {
+    // The fence must be longer than any run of backticks in the message
+    let longest = message.split(|c| c != '`').map(str::len).max().unwrap_or(0);
+    let fence = "`".repeat(longest.max(2) + 1);
+    let indented = message
+        .lines()
+        .map(|line| format!("  {}", line))
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    format!("  {}\n{}\n  {}\n", fence, indented, fence)
+}
+
+/// Reports a run as a Markdown summary, to post as a comment on a pull
+/// request.
+pub struct MarkdownReporter {
+    config: config::Config,
+}
+
+impl MarkdownReporter {
+    pub fn new(config: config::Config) -> Self {
+        Self { config }
+    }
+}
+
+impl Reporter for MarkdownReporter {
+    fn on(&self, report_event: &ReportEvent) -> Option<String> {
+        match report_event {
+            ReportEvent::TestSuiteCompleted(report) => Some(markdown_summary(report, &self.config)),
+            _ => None,
+        }
+    }
+}
+
+/// Reports a run as a single HTML page, with a collapsible section per test
+/// file that can be filtered by outcome.
+pub struct HtmlReporter {
+    config: config::Config,
+}
+
+const HTML_STYLE: &str = "
+body { font-family: system-ui, sans-serif; margin: 2em; color: #1f2328; }
+table { border-collapse: collapse; margin-bottom: 1em; }
+th, td { border: 1px solid #d0d7de; padding: 0.3em 0.8em; text-align: left; }
+pre { background: #f6f8fa; padding: 0.5em; overflow-x: auto; }
+details { border: 1px solid #d0d7de; border-radius: 6px; margin: 0.5em 0; padding: 0.5em 1em; }
+summary { cursor: pointer; font-weight: bold; }
+ul { list-style: none; padding-left: 1em; }
+.passed > summary .status, .check.passed .status { color: #1a7f37; }
+.failed > summary .status, .check.failed .status { color: #cf222e; }
+.location, .elapsed { color: #656d76; font-weight: normal; font-size: 0.9em; }
+.filters { margin: 1em 0; }
+#filter-failed:checked ~ .files .file.passed,
+#filter-passed:checked ~ .files .file.failed { display: none; }
+";
+
+impl HtmlReporter {
+    pub fn new(config: config::Config) -> Self {
+        Self { config }
+    }
+
+    fn format_page(&self, report: &TestSuiteReport) -> String {
+        let mut output = String::new();
+        output.push_str("<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n");
+        output.push_str("<title>nix-tests report</title>\n");
+        output.push_str(&format!("<style>{}</style>\n</head>\n<body>\n", HTML_STYLE));
+
+        let passed = !report.has_issues();
+        output.push_str(&format!(
+            "<h1>nix-tests: {}</h1>\n",
+            if report.processed_files() == 0 {
+                "No test files found"
+            } else if passed {
+                "All tests passed"
+            } else {
+                "Tests failed"
+            }
+        ));
+
+        output.push_str("<table class=\"counts\">\n<tr><th>Succeeded</th><th>Failed</th><th>Errored</th><th>Timed out</th><th>Resource exceeded</th><th>Total time</th></tr>\n");
+        output.push_str(&format!(
+            "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}ms</td></tr>\n</table>\n",
+            report.succeeded_files(),
+            report.failed_files(),
+            report.errored_files(),
+            report.timed_out_files(),
+            report.resource_exceeded_files(),
+            report.total_elapsed()
+        ));
+        if let Some(shard) = &report.shard {
+            output.push_str(&format!(
+                "<p>Shard {} of {}</p>\n",
+                shard.index, shard.total
+            ));
+        }
+        if let Some(seed) = report.seed {
+            output.push_str(&format!("<p>Seed: {}</p>\n", seed));
+        }
+
+        // The filters must precede the files for the CSS to hide them
+        output.push_str("<input type=\"radio\" name=\"filter\" id=\"filter-all\" checked><label for=\"filter-all\">All</label>\n");
+        output.push_str("<input type=\"radio\" name=\"filter\" id=\"filter-failed\"><label for=\"filter-failed\">Failed</label>\n");
+        output.push_str("<input type=\"radio\" name=\"filter\" id=\"filter-passed\"><label for=\"filter-passed\">Passed</label>\n");
+
+        output.push_str("<div class=\"files\">\n");
+        for file_report in report
+            .reports
+            .iter()
+            .filter(|file_report| !self.config.should_hide_test_report(file_report))
+        {
+            self.format_file(&mut output, file_report);
+        }
+        output.push_str("</div>\n</body>\n</html>\n");
+
+        output
+    }
+
+    fn format_file(&self, output: &mut String, report: &TestFileReport) {
+        let (class, status) = match report {
+            TestFileReport::Completed(report) if report.failed_count() == 0 => ("passed", "PASSED"),
+            TestFileReport::Completed(_) => ("failed", "FAILED"),
+            TestFileReport::Errored(_) => ("failed", "ERROR"),
+            TestFileReport::TimedOut(_) => ("failed", "TIMEOUT"),
+            TestFileReport::ResourceExceeded(_) => ("failed", "RESOURCE EXCEEDED"),
+        };
+        let cached = matches!(report, TestFileReport::Completed(report) if report.cached);
+
+        output.push_str(&format!(
+            "<details class=\"file {}\"{}>\n<summary><span class=\"status\">{}</span> {} <span class=\"elapsed\">({}{}ms)</span></summary>\n",
+            class,
+            if class == "failed" { " open" } else { "" },
+            status,
+            escape_html(report.file()),
+            if cached { "cached, " } else { "" },
+            report.elapsed()
+        ));
+
+        match report {
+            TestFileReport::Completed(report) => {
+                output.push_str("<ul>\n");
+                for test in &report.tests {
+                    output.push_str(&format!(
+                        "<li class=\"test\">{} <span class=\"location\">{}</span>{}\n<ul>\n",
+                        escape_html(&test.path.join(" -> ")),
+                        escape_html(&test.location),
+                        test.elapsed
+                            .map(|elapsed| format!(
+                                " <span class=\"elapsed\">({}ms)</span>",
+                                elapsed
+                            ))
+                            .unwrap_or_default()
+                    ));
+                    for check in &test.checks {
+                        self.format_check(output, check);
+                    }
+                    output.push_str("</ul>\n</li>\n");
+                }
+                output.push_str("</ul>\n");
+            }
+            TestFileReport::Errored(report) => {
+                output.push_str(&format!("<pre>{}</pre>\n", escape_html(&report.error)));
+            }
+            TestFileReport::TimedOut(report) => {
+                output.push_str(&format!("<p>Exceeded {}ms limit</p>\n", report.timeout));
+            }
+            TestFileReport::ResourceExceeded(report) => {
+                output.push_str(&format!("<p>Exceeded {} limit</p>\n", report.limit));
+            }
+        }
+
+        output.push_str("</details>\n");
+    }
+
+    fn format_check(&self, output: &mut String, check: &CheckReport) {
+        let (class, mark) = if check.success {
+            ("passed", "✓")
+        } else {
+            ("failed", "✗")
+        };
+        output.push_str(&format!(
+            "<li class=\"check {}\"><span class=\"status\">{}</span> {} <span class=\"location\">{}</span>",
+            class,
+            mark,
+            escape_html(&check.name),
+            escape_html(&check.location)
+        ));
+
+        if !check.success {
+            let mut failure = check.failure.clone().unwrap_or_default();
+            if let Some(property) = &check.property {
+                failure.push_str(&format!(
+                    "\nInput: {}\nSeed: {} ({} iteration(s), {} shrink(s))",
+                    property.input, property.seed, property.iterations, property.shrinks
+                ));
+            }
+            if !check.definitions.is_empty() {
+                failure.push_str(&format!(
+                    "\nDefined in:\n  {}",
+                    check.definitions.join("\n  ")
+                ));
+            }
+            if !failure.is_empty() {
+                output.push_str(&format!(
+                    "\n<pre>{}</pre>",
+                    escape_html(failure.trim_start())
+                ));
+            }
+        }
+
+        output.push_str("</li>\n");
+    }
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+impl Reporter for HtmlReporter {
+    fn on(&self, report_event: &ReportEvent) -> Option<String> {
+        match report_event {
+            ReportEvent::TestSuiteCompleted(report) => Some(self.format_page(report)),
+            _ => None,
+        }
+    }
+}
+
+pub struct ConfigurableReporter {
+    inner: Box<dyn Reporter + Send + Sync>,
+    /// Where the report goes instead of stdout, with its path
+    output: Option<(String, Mutex<File>)>,
+}
+
+impl ConfigurableReporter {
+    pub fn new(report_config: &config::Config) -> anyhow::Result<Self> {
+        let github_actions = std::env::var("GITHUB_ACTIONS").is_ok_and(|value| value == "true");
+
+        let inner: Box<dyn Reporter + Send + Sync> =
+            match report_config.format.resolve(github_actions) {
+                Format::Human | Format::Auto => Box::new(HumanReporter::new(report_config.clone())),
+                Format::Json => Box::new(JsonReporter::new(report_config.clone())),
+                Format::Quickfix => Box::new(QuickfixReporter::new(report_config.clone())),
+                Format::Github => Box::new(GithubReporter::from_env(report_config.clone())),
+                Format::Html => Box::new(HtmlReporter::new(report_config.clone())),
+                Format::Markdown => Box::new(MarkdownReporter::new(report_config.clone())),
+            };
+
+        let output = match &report_config.output {
+            Some(path) => {
+                let file = File::create(path)
+                    .context(format!("Failed to create report file: {}", path))?;
+                Some((path.clone(), Mutex::new(file)))
+            }
+            None => None,
+        };
+
+        Ok(Self { inner, output })
+    }
+}
+
+impl Reporter for ConfigurableReporter {
+    fn on(&self, report_event: &ReportEvent) -> Option<String> {
+        let output = self.inner.on(report_event);
+        let stdout = self.inner.on_stdout(report_event);
+        let Some((path, file)) = &self.output else {
+            return concat(output, stdout);
+        };
+
+        if let Some(message) = output {
+            if let Err(err) = file.lock().unwrap().write_all(message.as_bytes()) {
+                return concat(
+                    stdout,
+                    Some(format!(
+                        "Warning: Failed to write report to {}: {}\n",
+                        path, err
+                    )),
+                );
+            }
+        }
+
+        let written = match report_event {
+            ReportEvent::TestSuiteCompleted(_) => Some(format!("Report written to {}\n", path)),
+            _ => None,
+        };
+        concat(stdout, written)
+    }
+}
+
+#[cfg(test)]
+mod test_suite_report_tests {
+    use assert2::check;
+
+    use super::test_helpers::*;
+
+    #[test]
+    fn it_counts_files() {
+        let report = test_suite_report(
+            vec![
+                completed_test_file("file1.nix", 0, vec![]),
+                completed_test_file("file2.nix", 0, vec![]),
+                errored_test_file("file3.nix", "error", 0),
+                errored_test_file("file4.nix", "error", 0),
+                errored_test_file("file5.nix", "error", 0),
+                completed_test_file(
+                    "file6.nix",
+                    0,
+                    vec![failed_test_report(
+                        vec!["test"],
+                        "file:1",
+                        vec![failed_check_report_with_message("check", "failed")],
+                    )],
+                ),
+            ],
+            0,
+        );
+
+        check!(report.processed_files() == 6);
+        check!(report.succeeded_files() == 2);
+        check!(report.failed_files() == 1);
+        check!(report.errored_files() == 3);
+    }
+
+    #[test]
+    fn it_handles_empty_report() {
+        let report = test_suite_report(vec![], 0);
+
+        check!(report.processed_files() == 0);
         check!(report.succeeded_files() == 0);
         check!(report.failed_files() == 0);
         check!(report.errored_files() == 0);
@@ -506,6 +1717,396 @@ File: test.nix (75ms)
     Failed at my_test.nix:30
 FAILED (1 failed)
 
+"
+        );
+    }
+
+    #[test]
+    fn it_groups_test_cases_under_parent_test() {
+        let reporter = HumanReporter::new(Config::default());
+        let event = ReportEvent::TestFileCompleted(completed_test_file(
+            "test.nix",
+            60,
+            vec![
+                case_test_report(
+                    vec!["group", "systems", "x86_64-linux"],
+                    vec![passing_check_report("check")],
+                ),
+                case_test_report(
+                    vec!["group", "systems", "aarch64-linux"],
+                    vec![failed_check_report_with_message(
+                        "check",
+                        "Expected: 1\nGot: 2",
+                    )],
+                ),
+                passing_test_report(
+                    vec!["other"],
+                    "test.nix:40",
+                    vec![passing_check_report("check")],
+                ),
+            ],
+        ));
+
+        check!(
+            reporter.on(&event).unwrap()
+                == "\
+File: test.nix (60ms)
+group -> systems
+  ✓ x86_64-linux -> check
+  ✗ aarch64-linux -> check
+      Failure:
+        Expected: 1
+        Got: 2
+        at my_test.nix:30
+✓ other -> check
+FAILED (1 failed)
+
+"
+        );
+    }
+
+    #[test]
+    fn it_reports_property_counterexample() {
+        let reporter = HumanReporter::new(Config::default());
+        let event = ReportEvent::TestFileCompleted(completed_test_file(
+            "test.nix",
+            80,
+            vec![failed_test_report(
+                vec!["lists"],
+                "test.nix:10",
+                vec![CheckReport {
+                    property: Some(PropertyReport {
+                        seed: 42,
+                        iterations: 7,
+                        shrinks: 3,
+                        input: serde_json::json!([0, 1]),
+                    }),
+                    ..failed_check_report_with_message("is sorted", "Property does not hold")
+                }],
+            )],
+        ));
+
+        check!(
+            reporter.on(&event).unwrap()
+                == "\
+File: test.nix (80ms)
+✗ lists -> is sorted
+    Failure:
+      Property does not hold
+      Input: [0,1]
+      Seed: 42 (7 iteration(s), 3 shrink(s))
+      at my_test.nix:30
+FAILED (1 failed)
+
+"
+        );
+    }
+
+    #[test]
+    fn it_reports_option_definitions() {
+        let reporter = HumanReporter::new(Config::default());
+        let event = ReportEvent::TestFileCompleted(completed_test_file(
+            "test.nix",
+            90,
+            vec![failed_test_report(
+                vec!["module"],
+                "test.nix:10",
+                vec![CheckReport {
+                    definitions: vec![
+                        "/src/modules/base.nix".to_string(),
+                        "/src/modules/override.nix".to_string(),
+                    ],
+                    ..failed_check_report_with_message("port", "Expected: 80\nGot: 8080")
+                }],
+            )],
+        ));
+
+        check!(
+            reporter.on(&event).unwrap()
+                == "\
+File: test.nix (90ms)
+✗ module -> port
+    Failure:
+      Expected: 80
+      Got: 8080
+      Defined in:
+        /src/modules/base.nix
+        /src/modules/override.nix
+      at my_test.nix:30
+FAILED (1 failed)
+
+"
+        );
+    }
+
+    #[test]
+    fn it_marks_cached_results() {
+        let reporter = HumanReporter::new(Config::default());
+        let TestFileReport::Completed(report) = completed_test_file(
+            "test.nix",
+            90,
+            vec![passing_test_report(
+                vec!["cached"],
+                "test.nix:10",
+                vec![passing_check_report("check")],
+            )],
+        ) else {
+            unreachable!()
+        };
+        let event =
+            ReportEvent::TestFileCompleted(TestFileReport::Completed(TestFileCompletedReport {
+                cached: true,
+                ..report
+            }));
+
+        check!(
+            reporter.on(&event).unwrap()
+                == "\
+File: test.nix (cached, 90ms)
+✓ cached -> check
+
+"
+        );
+    }
+
+    #[test]
+    fn it_reports_exceeded_resource_limits() {
+        let reporter = HumanReporter::new(Config::default());
+        let event = ReportEvent::TestFileCompleted(TestFileReport::ResourceExceeded(
+            TestFileResourceExceededReport {
+                file: "hungry.nix".to_string(),
+                limit: ResourceLimit::Memory(2048),
+                elapsed: 300,
+                stats: None,
+            },
+        ));
+
+        check!(
+            reporter.on(&event).unwrap()
+                == "\
+File: hungry.nix (300ms)
+RESOURCE EXCEEDED: Exceeded 2048MiB memory limit
+
+"
+        );
+    }
+
+    #[test]
+    fn it_reports_resource_usage_with_stats() {
+        let reporter = HumanReporter::new(Config {
+            stats: true,
+            slowest: 0,
+            warn_slower_than: 0,
+            ..Config::default()
+        });
+        let event =
+            ReportEvent::TestFileCompleted(TestFileReport::Completed(TestFileCompletedReport {
+                stats: Some(ResourceStats {
+                    peak_memory: 256 * 1024 * 1024,
+                    user_time: 1200,
+                    system_time: 80,
+                    nix: Some(NixStats {
+                        thunks: 5000,
+                        function_calls: 1200,
+                        primop_calls: 300,
+                        gc_heap_size: 64 * 1024 * 1024,
+                    }),
+                }),
+                ..completed_test_file_report("test.nix", 1500)
+            }));
+
+        check!(
+            reporter.on(&event).unwrap()
+                == "\
+File: test.nix (1500ms)
+Stats: 256.0MiB peak memory, 1200ms user, 80ms system
+Nix: 5000 thunks, 1200 function calls, 300 primop calls, 64.0MiB GC heap
+
+"
+        );
+    }
+
+    #[test]
+    fn it_reports_resource_usage_totals_with_stats() {
+        let reporter = HumanReporter::new(Config {
+            stats: true,
+            slowest: 0,
+            warn_slower_than: 0,
+            ..Config::default()
+        });
+        let stats = |peak_memory: u64, user_time: u64| ResourceStats {
+            peak_memory,
+            user_time,
+            system_time: 10,
+            nix: None,
+        };
+        let event = ReportEvent::TestSuiteCompleted(test_suite_report(
+            vec![
+                TestFileReport::Completed(TestFileCompletedReport {
+                    stats: Some(stats(100 * 1024 * 1024, 500)),
+                    ..completed_test_file_report("test1.nix", 50)
+                }),
+                TestFileReport::Completed(TestFileCompletedReport {
+                    stats: Some(stats(300 * 1024 * 1024, 700)),
+                    ..completed_test_file_report("test2.nix", 50)
+                }),
+            ],
+            100,
+        ));
+
+        check!(
+            reporter.on(&event).unwrap()
+                == "\
+All tests passed (100ms)
+Peak memory: 300.0MiB
+CPU time: 1200ms user, 20ms system
+"
+        );
+    }
+
+    #[test]
+    fn it_reports_files_without_resource_usage_with_stats() {
+        let reporter = HumanReporter::new(Config {
+            stats: true,
+            slowest: 0,
+            warn_slower_than: 0,
+            ..Config::default()
+        });
+        let file = ReportEvent::TestFileCompleted(TestFileReport::Completed(
+            completed_test_file_report("test.nix", 10),
+        ));
+        let suite = ReportEvent::TestSuiteCompleted(test_suite_report(
+            vec![
+                TestFileReport::Completed(TestFileCompletedReport {
+                    stats: Some(ResourceStats {
+                        peak_memory: 100 * 1024 * 1024,
+                        user_time: 500,
+                        system_time: 10,
+                        nix: None,
+                    }),
+                    ..completed_test_file_report("test1.nix", 50)
+                }),
+                TestFileReport::Completed(completed_test_file_report("test2.nix", 50)),
+            ],
+            100,
+        ));
+
+        check!(reporter.on(&file).unwrap() == "File: test.nix (10ms)\nStats: not measured\n\n");
+        check!(
+            reporter.on(&suite).unwrap()
+                == "\
+All tests passed (100ms)
+Peak memory: 100.0MiB
+CPU time: 500ms user, 10ms system
+Stats not measured for 1 file(s)
+"
+        );
+    }
+
+    #[test]
+    fn it_hides_resource_usage_by_default() {
+        let reporter = HumanReporter::new(Config::default());
+        let event =
+            ReportEvent::TestFileCompleted(TestFileReport::Completed(TestFileCompletedReport {
+                stats: Some(ResourceStats::default()),
+                ..completed_test_file_report("test.nix", 10)
+            }));
+
+        check!(reporter.on(&event).unwrap() == "File: test.nix (10ms)\n\n");
+    }
+
+    #[test]
+    fn it_reports_slowest_tests_when_profiled() {
+        let reporter = HumanReporter::new(Config {
+            slowest: 2,
+            ..Config::default()
+        });
+        let timed = |path: &str, elapsed: u128| TestReport {
+            elapsed: Some(elapsed),
+            ..passing_test_report(vec![path], "test.nix:1", vec![])
+        };
+        let event = ReportEvent::TestSuiteCompleted(test_suite_report(
+            vec![completed_test_file(
+                "test.nix",
+                500,
+                vec![timed("fast", 5), timed("slow", 300), timed("medium", 40)],
+            )],
+            500,
+        ));
+
+        check!(
+            reporter.on(&event).unwrap()
+                == "\
+All tests passed (500ms)
+Slowest files:
+  500ms test.nix
+Slowest tests:
+  300ms test.nix: slow
+  40ms test.nix: medium
+"
+        );
+    }
+
+    #[test]
+    fn it_warns_about_files_slower_than_the_budget() {
+        let reporter = HumanReporter::new(Config {
+            warn_slower_than: 1000,
+            ..Config::default()
+        });
+        let event = ReportEvent::TestFileCompleted(completed_test_file("slow.nix", 1500, vec![]));
+
+        check!(
+            reporter.on(&event).unwrap()
+                == "\
+File: slow.nix (1500ms)
+WARNING: Slower than 1000ms
+
+"
+        );
+    }
+
+    #[test]
+    fn it_reports_slowest_files_and_budget_warnings_in_summary() {
+        let reporter = HumanReporter::new(Config {
+            slowest: 2,
+            warn_slower_than: 60,
+            ..Config::default()
+        });
+        let event = ReportEvent::TestSuiteCompleted(test_suite_report(
+            vec![
+                completed_test_file("fast.nix", 10, vec![]),
+                completed_test_file("slow.nix", 75, vec![]),
+                errored_test_file("broken.nix", "error", 50),
+            ],
+            150,
+        ));
+
+        check!(
+            reporter.on(&event).unwrap()
+                == "\
+2 file(s) succeeded
+1 file(s) had errors
+Total time: 150ms
+Warning: 1 file(s) slower than 60ms
+Slowest files:
+  75ms slow.nix
+  50ms broken.nix
+"
+        );
+    }
+
+    #[test]
+    fn it_reports_shuffle_seed_in_summary() {
+        let reporter = HumanReporter::new(Config::default());
+        let event = ReportEvent::TestSuiteCompleted(
+            test_suite_report(vec![completed_test_file("a.nix", 10, vec![])], 10).with_seed(42),
+        );
+
+        check!(
+            reporter.on(&event).unwrap()
+                == "\
+All tests passed (10ms)
+Seed: 42
 "
         );
     }
@@ -562,8 +2163,34 @@ All tests passed (100ms)
             reporter.on(&event).unwrap()
                 == "\
 1 file(s) succeeded
-1 file(s) failed
-Total time: 200ms
+1 file(s) failed
+Total time: 200ms
+"
+        );
+    }
+
+    #[test]
+    fn it_reports_test_suite_completed_with_exceeded_resource_limits() {
+        let reporter = HumanReporter::new(Config::default());
+        let event = ReportEvent::TestSuiteCompleted(test_suite_report(
+            vec![
+                completed_test_file("test1.nix", 50, vec![]),
+                TestFileReport::ResourceExceeded(TestFileResourceExceededReport {
+                    file: "hungry.nix".to_string(),
+                    limit: ResourceLimit::OpenFiles(64),
+                    elapsed: 25,
+                    stats: None,
+                }),
+            ],
+            150,
+        ));
+
+        check!(
+            reporter.on(&event).unwrap()
+                == "\
+1 file(s) succeeded
+1 file(s) exceeded resource limits
+Total time: 150ms
 "
         );
     }
@@ -627,6 +2254,10 @@ Total time: 250ms
             hide_succeeded: true,
             hide_failed: false,
             hide_errored: false,
+            stats: false,
+            slowest: 0,
+            warn_slower_than: 0,
+            output: None,
         };
         let reporter = HumanReporter::new(config);
         let event = ReportEvent::TestFileCompleted(completed_test_file("test.nix", 50, vec![]));
@@ -641,6 +2272,10 @@ Total time: 250ms
             hide_succeeded: false,
             hide_failed: true,
             hide_errored: false,
+            stats: false,
+            slowest: 0,
+            warn_slower_than: 0,
+            output: None,
         };
         let reporter = HumanReporter::new(config);
         let event = ReportEvent::TestFileCompleted(completed_test_file(
@@ -663,6 +2298,10 @@ Total time: 250ms
             hide_succeeded: false,
             hide_failed: false,
             hide_errored: true,
+            stats: false,
+            slowest: 0,
+            warn_slower_than: 0,
+            output: None,
         };
         let reporter = HumanReporter::new(config);
         let event = ReportEvent::TestFileCompleted(errored_test_file("broken.nix", "error", 25));
@@ -677,6 +2316,10 @@ Total time: 250ms
             hide_succeeded: true,
             hide_failed: true,
             hide_errored: true,
+            stats: false,
+            slowest: 0,
+            warn_slower_than: 0,
+            output: None,
         };
         let reporter = HumanReporter::new(config);
         let event = ReportEvent::TestSuiteCompleted(test_suite_report(
@@ -725,11 +2368,106 @@ mod json_reporter_tests {
     }
 
     #[test]
-    fn it_returns_none_for_non_test_file_completed_events() {
+    fn it_returns_json_for_exceeded_resource_limits() {
         let reporter = JsonReporter::new(Config::default());
-        let event = ReportEvent::TestFileNotFound("test.nix".to_string());
+        let event = ReportEvent::TestFileCompleted(TestFileReport::ResourceExceeded(
+            TestFileResourceExceededReport {
+                file: "slow.nix".to_string(),
+                limit: ResourceLimit::CpuTime(30),
+                elapsed: 31000,
+                stats: None,
+            },
+        ));
 
-        check!(reporter.on(&event).is_none());
+        check!(
+            reporter.on(&event).unwrap()
+                == r#"{"version":1,"event":"test_file_completed","status":"resource_exceeded","file":"slow.nix","limit":"cpu_time","max":30,"elapsed":31000}"#
+                    .to_string()
+                    + "\n"
+        );
+    }
+
+    #[test]
+    fn it_reads_back_its_json() {
+        let reporter = JsonReporter::new(Config::default());
+        let reports = vec![
+            completed_test_file(
+                "test.nix",
+                75,
+                vec![failed_test_report(
+                    vec!["test"],
+                    "test.nix:1",
+                    vec![failed_check_report("check")],
+                )],
+            ),
+            errored_test_file("broken.nix", "error", 50),
+            TestFileReport::ResourceExceeded(TestFileResourceExceededReport {
+                file: "slow.nix".to_string(),
+                limit: ResourceLimit::Memory(2048),
+                elapsed: 31000,
+                stats: Some(ResourceStats {
+                    peak_memory: 1024,
+                    ..ResourceStats::default()
+                }),
+            }),
+        ];
+
+        for report in reports {
+            let output = reporter
+                .on(&ReportEvent::TestFileCompleted(report.clone()))
+                .unwrap();
+            check!(serde_json::from_str::<TestFileReport>(&output).unwrap() == report);
+        }
+    }
+
+    #[test]
+    fn it_returns_json_summary_for_test_suite_completed() {
+        let reporter = JsonReporter::new(Config {
+            hide_succeeded: true,
+            ..Config::default()
+        });
+        let event = ReportEvent::TestSuiteCompleted(
+            test_suite_report(
+                vec![
+                    completed_test_file("a.nix", 10, vec![]),
+                    errored_test_file("b.nix", "error", 20),
+                ],
+                30,
+            )
+            .with_shard(Shard { index: 2, total: 3 })
+            .with_seed(42),
+        );
+
+        check!(
+            reporter.on(&event).unwrap()
+                == r#"{"version":1,"event":"test_suite_completed","status":"summary","shard":{"index":2,"total":3},"seed":42,"elapsed":30,"counts":{"succeeded":1,"failed":0,"errored":1,"timed_out":0,"resource_exceeded":0},"files":["a.nix","b.nix"]}"#
+                    .to_string()
+                    + "\n"
+        );
+    }
+
+    #[test]
+    fn it_returns_json_for_skipped_test_files() {
+        let reporter = JsonReporter::new(Config::default());
+
+        check!(
+            reporter
+                .on(&ReportEvent::TestFileNotFound("missing.nix".to_string()))
+                .unwrap()
+                == "{\"version\":1,\"event\":\"test_file_not_found\",\"file\":\"missing.nix\"}\n"
+        );
+        check!(
+            reporter
+                .on(&ReportEvent::TestFileInvalid("invalid.nix".to_string()))
+                .unwrap()
+                == "{\"version\":1,\"event\":\"test_file_invalid\",\"file\":\"invalid.nix\"}\n"
+        );
+    }
+
+    #[test]
+    fn it_matches_the_published_schema() {
+        // Regenerate with `nix-tests schema > schema/events.schema.json`
+        check!(json_schema() == include_str!("../schema/events.schema.json"));
     }
 
     #[test]
@@ -739,6 +2477,10 @@ mod json_reporter_tests {
             hide_succeeded: true,
             hide_failed: false,
             hide_errored: false,
+            stats: false,
+            slowest: 0,
+            warn_slower_than: 0,
+            output: None,
         };
         let reporter = JsonReporter::new(config);
         let event = ReportEvent::TestFileCompleted(completed_test_file("test.nix", 50, vec![]));
@@ -753,6 +2495,10 @@ mod json_reporter_tests {
             hide_succeeded: false,
             hide_failed: true,
             hide_errored: false,
+            stats: false,
+            slowest: 0,
+            warn_slower_than: 0,
+            output: None,
         };
         let reporter = JsonReporter::new(config);
         let event = ReportEvent::TestFileCompleted(completed_test_file(
@@ -775,6 +2521,10 @@ mod json_reporter_tests {
             hide_succeeded: false,
             hide_failed: false,
             hide_errored: true,
+            stats: false,
+            slowest: 0,
+            warn_slower_than: 0,
+            output: None,
         };
         let reporter = JsonReporter::new(config);
         let event = ReportEvent::TestFileCompleted(errored_test_file("broken.nix", "error", 25));
@@ -789,6 +2539,10 @@ mod json_reporter_tests {
             hide_succeeded: false,
             hide_failed: true,
             hide_errored: true,
+            stats: false,
+            slowest: 0,
+            warn_slower_than: 0,
+            output: None,
         };
         let reporter = JsonReporter::new(config);
         let event = ReportEvent::TestFileCompleted(completed_test_file("test.nix", 50, vec![]));
@@ -797,17 +2551,528 @@ mod json_reporter_tests {
     }
 }
 
+#[cfg(test)]
+mod quickfix_reporter_tests {
+    use assert2::check;
+
+    use crate::reports::config::Config;
+
+    use super::test_helpers::*;
+    use super::*;
+
+    #[test]
+    fn it_reports_failed_checks_at_their_location() {
+        let reporter = QuickfixReporter::new(Config::default());
+        let report = completed_test_file(
+            "my_test.nix",
+            50,
+            vec![failed_test_report(
+                vec!["group", "test"],
+                "my_test.nix:20",
+                vec![
+                    passing_check_report("passes"),
+                    failed_check_report_with_message("is equal", "Expected: 1\nGot: 2"),
+                    failed_check_report("is true"),
+                ],
+            )],
+        );
+
+        check!(
+            reporter.on(&ReportEvent::TestFileCompleted(report))
+                == Some(
+                    "my_test.nix:30: group -> test -> is equal: Expected: 1; Got: 2\n\
+                     my_test.nix:30: group -> test -> is true: Check failed\n"
+                        .to_string()
+                )
+        );
+    }
+
+    #[test]
+    fn it_reports_errors_at_the_innermost_location() {
+        let reporter = QuickfixReporter::new(Config::default());
+        let error = "error:
+       … while evaluating the attribute 'tests'
+         at /project/lib.nix:12:3:
+       error: undefined variable 'foo'
+       at /project/my_test.nix:5:10:
+            4|
+            5|   value = foo;
+             |          ^";
+
+        check!(
+            reporter.on(&ReportEvent::TestFileCompleted(errored_test_file(
+                "my_test.nix",
+                error,
+                10
+            ))) == Some("/project/my_test.nix:5: undefined variable 'foo'\n".to_string())
+        );
+        check!(
+            reporter.on(&ReportEvent::TestFileCompleted(errored_test_file(
+                "my_test.nix",
+                "Failed to execute nix-instantiate",
+                10
+            ))) == Some("my_test.nix:1: Failed to execute nix-instantiate\n".to_string())
+        );
+    }
+
+    #[test]
+    fn it_reports_nothing_else() {
+        let reporter = QuickfixReporter::new(Config::default());
+        let succeeded = completed_test_file(
+            "my_test.nix",
+            50,
+            vec![passing_test_report(
+                vec!["test"],
+                "my_test.nix:20",
+                vec![passing_check_report("passes")],
+            )],
+        );
+
+        check!(reporter
+            .on(&ReportEvent::TestFileCompleted(succeeded.clone()))
+            .is_none());
+        check!(reporter
+            .on(&ReportEvent::TestFileNotFound("missing.nix".to_string()))
+            .is_none());
+        check!(reporter
+            .on(&ReportEvent::TestSuiteCompleted(test_suite_report(
+                vec![succeeded],
+                50
+            )))
+            .is_none());
+    }
+}
+
+#[cfg(test)]
+mod github_reporter_tests {
+    use assert2::{check, let_assert};
+    use tempfile::TempDir;
+
+    use crate::reports::config::Config;
+
+    use super::test_helpers::*;
+    use super::*;
+
+    fn config() -> Config {
+        Config {
+            hide_failed: true,
+            hide_errored: true,
+            ..Default::default()
+        }
+    }
+
+    fn reporter(step_summary: Option<PathBuf>) -> GithubReporter {
+        GithubReporter::new(config(), Some(PathBuf::from("/work/project")), step_summary)
+    }
+
+    fn failed_file() -> TestFileReport {
+        let mut check = failed_check_report_with_message("is equal", "Expected: 1\nGot: 2");
+        check.location = "/work/project/tests/my_test.nix:30".to_string();
+        completed_test_file(
+            "tests/my_test.nix",
+            50,
+            vec![failed_test_report(
+                vec!["group", "test"],
+                "/work/project/tests/my_test.nix:20",
+                vec![check, passing_check_report("passes")],
+            )],
+        )
+    }
+
+    #[test]
+    fn it_annotates_failed_checks() {
+        check!(
+            reporter(None).on_stdout(&ReportEvent::TestFileCompleted(failed_file()))
+                == Some(
+                    "::error file=tests/my_test.nix,line=30,title=group -> test -> is equal::Expected: 1%0AGot: 2\n"
+                        .to_string()
+                )
+        );
+    }
+
+    #[test]
+    fn it_annotates_errored_files() {
+        let error = "error: undefined variable 'foo'\n       at /work/project/tests/a,b.nix:5:10:";
+
+        check!(
+            reporter(None).on_stdout(&ReportEvent::TestFileCompleted(errored_test_file(
+                "./tests/a,b.nix",
+                error,
+                10
+            ))) == Some(format!(
+                "::error file=tests/a%2Cb.nix,line=5,title=Evaluation error::{}\n",
+                escape_data(error)
+            ))
+        );
+        check!(
+            reporter(None).on_stdout(&ReportEvent::TestFileCompleted(errored_test_file(
+                "./tests/my_test.nix",
+                "Failed to execute nix-instantiate",
+                10
+            ))) == Some(
+                "::error file=tests/my_test.nix,line=1,title=Evaluation error::Failed to execute nix-instantiate\n"
+                    .to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn it_writes_a_job_summary() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("summary.md");
+        std::fs::write(&path, "Earlier step\n").unwrap();
+        let report = test_suite_report(
+            vec![
+                failed_file(),
+                errored_test_file("tests/b_test.nix", "boom", 10),
+            ],
+            60,
+        );
+
+        let output = reporter(Some(path.clone()))
+            .on_stdout(&ReportEvent::TestSuiteCompleted(report.clone()));
+
+        check!(output.is_none());
+        check!(
+            std::fs::read_to_string(&path).unwrap()
+                == format!("Earlier step\n{}", markdown_summary(&report, &config()))
+        );
+    }
+
+    #[test]
+    fn it_annotates_on_stdout_when_writing_the_report_to_a_file() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("report.txt");
+        let reporter = ConfigurableReporter {
+            inner: Box::new(GithubReporter::new(
+                Config::default(),
+                Some(PathBuf::from("/work/project")),
+                None,
+            )),
+            output: Some((
+                path.to_str().unwrap().to_string(),
+                Mutex::new(File::create(&path).unwrap()),
+            )),
+        };
+
+        let output = reporter.on(&ReportEvent::TestFileCompleted(failed_file()));
+
+        let_assert!(Some(output) = output);
+        check!(output.starts_with("::error file=tests/my_test.nix,line=30,"));
+        let written = std::fs::read_to_string(&path).unwrap();
+        check!(written.starts_with("File: tests/my_test.nix (50ms)\n"));
+        check!(!written.contains("::error"));
+    }
+
+    #[test]
+    fn it_summarises_a_run_as_markdown() {
+        let report = test_suite_report(
+            vec![
+                failed_file(),
+                errored_test_file("tests/b_test.nix", "boom", 10),
+            ],
+            60,
+        )
+        .with_seed(42);
+
+        check!(
+            markdown_summary(&report, &Config::default())
+                == "## nix-tests: Tests failed
+
+| Succeeded | Failed | Errored | Timed out | Resource exceeded | Time |
+| --- | --- | --- | --- | --- | --- |
+| 0 | 1 | 1 | 0 | 0 | 60ms |
+
+Seed: 42
+
+### Failures
+
+- **group -> test -> is equal** at `/work/project/tests/my_test.nix:30`
+  ```
+  Expected: 1
+  Got: 2
+  ```
+- **Error** in `tests/b_test.nix`
+  ```
+  boom
+  ```
+"
+        );
+    }
+
+    #[test]
+    fn it_selects_github_in_github_actions() {
+        check!(Format::default() == Format::Auto);
+        check!(Format::Auto.resolve(true) == Format::Github);
+        check!(Format::Auto.resolve(false) == Format::Human);
+        check!(Format::Json.resolve(true) == Format::Json);
+    }
+}
+
+#[cfg(test)]
+mod html_reporter_tests {
+    use assert2::check;
+    use tempfile::TempDir;
+
+    use crate::reports::config::Config;
+
+    use super::test_helpers::*;
+    use super::*;
+
+    fn suite() -> TestSuiteReport {
+        test_suite_report(
+            vec![
+                completed_test_file(
+                    "passing_test.nix",
+                    20,
+                    vec![passing_test_report(
+                        vec!["passes"],
+                        "passing_test.nix:3",
+                        vec![passing_check_report("is true")],
+                    )],
+                ),
+                completed_test_file(
+                    "failing_test.nix",
+                    30,
+                    vec![failed_test_report(
+                        vec!["group", "fails"],
+                        "failing_test.nix:5",
+                        vec![failed_check_report_with_message(
+                            "is equal",
+                            "Expected: <a>\nGot: <b>",
+                        )],
+                    )],
+                ),
+                errored_test_file("errored_test.nix", "syntax error", 10),
+            ],
+            60,
+        )
+    }
+
+    #[test]
+    fn it_writes_a_page_once_the_run_completes() {
+        let reporter = HtmlReporter::new(Config::default());
+
+        check!(reporter
+            .on(&ReportEvent::TestFileCompleted(errored_test_file(
+                "errored_test.nix",
+                "syntax error",
+                10
+            )))
+            .is_none());
+
+        let page = reporter
+            .on(&ReportEvent::TestSuiteCompleted(suite()))
+            .unwrap();
+        check!(page.starts_with("<!DOCTYPE html>"));
+        check!(page.contains("<h1>nix-tests: Tests failed</h1>"));
+        check!(page
+            .contains("<tr><td>1</td><td>1</td><td>1</td><td>0</td><td>0</td><td>60ms</td></tr>"));
+        check!(page.contains("<details class=\"file passed\">\n<summary><span class=\"status\">PASSED</span> passing_test.nix"));
+        check!(page.contains("<details class=\"file failed\" open>\n<summary><span class=\"status\">FAILED</span> failing_test.nix"));
+        check!(page.contains("<pre>Expected: &lt;a&gt;\nGot: &lt;b&gt;</pre>"));
+        check!(page.contains("<pre>syntax error</pre>"));
+        check!(page.contains("id=\"filter-failed\""));
+    }
+
+    #[test]
+    fn it_leaves_out_hidden_test_files() {
+        let reporter = HtmlReporter::new(Config {
+            hide_succeeded: true,
+            ..Default::default()
+        });
+
+        let page = reporter
+            .on(&ReportEvent::TestSuiteCompleted(suite()))
+            .unwrap();
+        check!(!page.contains("passing_test.nix"));
+        check!(page.contains("failing_test.nix"));
+    }
+
+    #[test]
+    fn it_writes_the_report_to_the_output_file() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("report.html").to_str().unwrap().to_string();
+        let reporter = ConfigurableReporter::new(&Config {
+            format: Format::Html,
+            output: Some(path.clone()),
+            ..Default::default()
+        })
+        .unwrap();
+
+        check!(
+            reporter.on(&ReportEvent::TestSuiteCompleted(suite()))
+                == Some(format!("Report written to {}\n", path))
+        );
+        check!(
+            std::fs::read_to_string(&path).unwrap()
+                == HtmlReporter::new(Config::default()).format_page(&suite())
+        );
+    }
+}
+
+#[cfg(test)]
+mod markdown_reporter_tests {
+    use assert2::check;
+
+    use crate::reports::config::Config;
+
+    use super::test_helpers::*;
+    use super::*;
+
+    #[test]
+    fn it_writes_a_summary_once_the_run_completes() {
+        let report = test_suite_report(
+            vec![completed_test_file(
+                "my_test.nix",
+                50,
+                vec![passing_test_report(
+                    vec!["test"],
+                    "my_test.nix:20",
+                    vec![passing_check_report("passes")],
+                )],
+            )],
+            50,
+        );
+        let reporter = MarkdownReporter::new(Config::default());
+
+        check!(reporter
+            .on(&ReportEvent::TestFileCompleted(report.reports()[0].clone()))
+            .is_none());
+        check!(
+            reporter.on(&ReportEvent::TestSuiteCompleted(report.clone()))
+                == Some(markdown_summary(&report, &Config::default()))
+        );
+    }
+
+    #[test]
+    fn it_leaves_out_failures_of_hidden_test_files() {
+        let report = test_suite_report(
+            vec![
+                errored_test_file("errored_test.nix", "boom", 10),
+                completed_test_file(
+                    "failed_test.nix",
+                    10,
+                    vec![failed_test_report(
+                        vec!["test"],
+                        "failed_test.nix:2",
+                        vec![failed_check_report_with_message("check", "bang")],
+                    )],
+                ),
+            ],
+            20,
+        );
+        let config = Config {
+            hide_errored: true,
+            ..Default::default()
+        };
+
+        let summary = markdown_summary(&report, &config);
+        check!(summary.contains("| 0 | 1 | 1 | 0 | 0 | 20ms |"));
+        check!(!summary.contains("errored_test.nix"));
+        check!(summary.contains("bang"));
+    }
+
+    #[test]
+    fn it_lists_the_slowest_files_and_tests() {
+        let mut slow = passing_test_report(vec!["slow"], "a.nix:2", vec![]);
+        slow.elapsed = Some(40);
+        let report = test_suite_report(
+            vec![
+                completed_test_file("a.nix", 50, vec![slow]),
+                completed_test_file("b.nix", 10, vec![]),
+            ],
+            60,
+        );
+        let config = Config {
+            slowest: 1,
+            ..Default::default()
+        };
+
+        check!(markdown_summary(&report, &config).ends_with(
+            "
+### Slowest files
+
+- 50ms `a.nix`
+
+### Slowest tests
+
+- 40ms `a.nix`: slow
+"
+        ));
+    }
+
+    #[test]
+    fn it_fences_failures_containing_backticks() {
+        let report = test_suite_report(
+            vec![errored_test_file("my_test.nix", "```nix\nboom\n```", 10)],
+            10,
+        );
+
+        check!(markdown_summary(&report, &Config::default())
+            .contains("\n  ````\n  ```nix\n  boom\n  ```\n  ````\n"));
+    }
+}
+
+#[cfg(test)]
+mod test_file_listing_tests {
+    use assert2::check;
+
+    use super::*;
+
+    fn listing() -> TestFileListing {
+        TestFileListing {
+            file: "test.nix".to_string(),
+            tests: Some(vec![TestListing {
+                path: vec!["group".to_string(), "test".to_string()],
+                case: None,
+                location: "test.nix:3".to_string(),
+                checks: vec![CheckListing {
+                    name: "is equal".to_string(),
+                    location: "test.nix:4".to_string(),
+                }],
+            }]),
+            error: None,
+        }
+    }
+
+    #[test]
+    fn it_lists_tests_with_their_checks() {
+        check!(
+            listing().human()
+                == "test.nix\n  group -> test (test.nix:3)\n    - is equal (test.nix:4)\n"
+        );
+    }
+
+    #[test]
+    fn it_returns_json_for_listings() {
+        check!(
+            listing().json()
+                == r#"{"file":"test.nix","tests":[{"path":["group","test"],"location":"test.nix:3","checks":[{"name":"is equal","location":"test.nix:4"}]}]}"#.to_string() + "\n"
+        );
+
+        let errored = TestFileListing {
+            file: "test.nix".to_string(),
+            tests: None,
+            error: Some("boom".to_string()),
+        };
+        check!(errored.json() == "{\"file\":\"test.nix\",\"error\":\"boom\"}\n");
+    }
+}
+
 #[cfg(test)]
 mod test_helpers {
     use super::*;
 
-    #[allow(dead_code)]
     pub fn passing_check_report(name: &str) -> CheckReport {
         CheckReport {
             name: name.to_string(),
             success: true,
             failure: None,
             location: "my_test.nix:30".to_string(),
+            property: None,
+            build: None,
+            definitions: vec![],
         }
     }
 
@@ -817,6 +3082,9 @@ mod test_helpers {
             success: false,
             failure: Some(failure.to_string()),
             location: "my_test.nix:30".to_string(),
+            property: None,
+            build: None,
+            definitions: vec![],
         }
     }
 
@@ -826,10 +3094,12 @@ mod test_helpers {
             success: false,
             failure: None,
             location: "my_test.nix:30".to_string(),
+            property: None,
+            build: None,
+            definitions: vec![],
         }
     }
 
-    #[allow(dead_code)]
     pub fn passing_test_report(
         path: Vec<&str>,
         location: &str,
@@ -838,8 +3108,21 @@ mod test_helpers {
         TestReport {
             success: checks.iter().all(|c| c.success),
             path: path.iter().map(|s| s.to_string()).collect(),
+            case: None,
             location: location.to_string(),
             checks,
+            elapsed: None,
+        }
+    }
+
+    pub fn case_test_report(path: Vec<&str>, checks: Vec<CheckReport>) -> TestReport {
+        TestReport {
+            success: checks.iter().all(|c| c.success),
+            path: path.iter().map(|s| s.to_string()).collect(),
+            case: path.last().map(|s| s.to_string()),
+            location: "test.nix:10".to_string(),
+            checks,
+            elapsed: None,
         }
     }
 
@@ -851,8 +3134,10 @@ mod test_helpers {
         TestReport {
             success: false,
             path: path.iter().map(|s| s.to_string()).collect(),
+            case: None,
             location: location.to_string(),
             checks,
+            elapsed: None,
         }
     }
 
@@ -865,14 +3150,25 @@ mod test_helpers {
             file: file.to_string(),
             elapsed,
             tests,
+            mocks: vec![],
+            cached: false,
+            stats: None,
         })
     }
 
+    pub fn completed_test_file_report(file: &str, elapsed: u128) -> TestFileCompletedReport {
+        let TestFileReport::Completed(report) = completed_test_file(file, elapsed, vec![]) else {
+            unreachable!()
+        };
+        report
+    }
+
     pub fn errored_test_file(file: &str, error: &str, elapsed: u128) -> TestFileReport {
         TestFileReport::Errored(TestFileErroredReport {
             file: file.to_string(),
             error: error.to_string(),
             elapsed,
+            stats: None,
         })
     }
 
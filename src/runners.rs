@@ -1,17 +1,49 @@
-use std::{env, future::Future, process::Stdio, sync::Arc, time::Duration};
+use std::{
+    collections::BTreeMap,
+    env,
+    future::Future,
+    hash::{Hash, Hasher},
+    os::unix::process::ExitStatusExt,
+    path::{Path, PathBuf},
+    process::Stdio,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
 
-use futures::{stream, StreamExt};
-use tokio::{process::Command, time::Instant};
+use anyhow::{bail, Context};
+
+use futures::{future, stream, StreamExt};
+use serde::{Deserialize, Serialize};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncReadExt, BufReader},
+    process::Command,
+    sync::{mpsc, Semaphore},
+    time::Instant,
+};
 
 use crate::{
+    config::cache_dir,
+    deps::dependencies,
     files::TestFile,
+    repl::ReplPool,
     reports::{
-        ReportEvent, Reporter, TestFileCompletedReport, TestFileErroredReport, TestFileReport,
-        TestFileTimedOutReport, TestReport, TestSuiteReport,
+        CheckReport, NixStats, ReportEvent, Reporter, ResourceLimit, ResourceStats,
+        TestFileCompletedReport, TestFileErroredReport, TestFileReport,
+        TestFileResourceExceededReport, TestFileTimedOutReport, TestListing, TestReport,
+        TestSuiteReport,
     },
+    shard::Shard,
 };
 
 pub mod config {
+    use std::{
+        collections::{hash_map::RandomState, BTreeMap},
+        hash::{BuildHasher, Hasher},
+    };
+
     use serde::{Deserialize, Serialize};
 
     #[derive(Default, Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
@@ -22,6 +54,99 @@ pub mod config {
 
         #[serde(default)]
         pub timeout: u64,
+
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub seed: Option<u32>,
+
+        #[serde(default)]
+        pub property_iterations: PropertyIterations,
+
+        #[serde(default)]
+        pub build_timeout: u64,
+
+        #[serde(default)]
+        pub build_jobs: BuildJobs,
+
+        #[serde(default, skip_serializing_if = "Overrides::is_empty")]
+        pub overrides: Overrides,
+
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub lib_path: Option<String>,
+
+        #[serde(default)]
+        pub evaluator: Evaluator,
+
+        #[serde(default)]
+        pub batch_size: BatchSize,
+
+        #[serde(default)]
+        pub cache: bool,
+
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub cache_dir: Option<String>,
+
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub max_memory: Option<u64>,
+
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub max_cpu_time: Option<u64>,
+
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub max_open_files: Option<u64>,
+
+        #[serde(default)]
+        pub profile: bool,
+
+        #[serde(default)]
+        pub order: Order,
+    }
+
+    #[derive(Default, Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+    #[serde(rename_all = "kebab-case")]
+    pub enum Order {
+        #[default]
+        Sorted,
+        Random,
+        SlowestFirst,
+    }
+
+    #[derive(Default, Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+    #[serde(rename_all = "kebab-case")]
+    pub enum Evaluator {
+        #[default]
+        Instantiate,
+        Repl,
+    }
+
+    #[derive(Default, Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+    #[serde(rename_all = "kebab-case")]
+    pub struct Overrides {
+        #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+        pub env: BTreeMap<String, String>,
+
+        #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+        pub builtins: BTreeMap<String, serde_json::Value>,
+
+        #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+        pub imports: BTreeMap<String, String>,
+    }
+
+    impl Overrides {
+        pub fn is_empty(&self) -> bool {
+            self.env.is_empty() && self.builtins.is_empty() && self.imports.is_empty()
+        }
+
+        pub fn names(&self) -> Vec<String> {
+            let env = self.env.keys().map(|name| format!("env.{name}"));
+            let builtins = self.builtins.keys().map(|name| format!("builtins.{name}"));
+            let imports = self.imports.keys().map(|name| format!("imports.{name}"));
+
+            env.chain(builtins).chain(imports).collect()
+        }
+    }
+
+    pub fn random_seed() -> u32 {
+        RandomState::new().build_hasher().finish() as u32
     }
 
     #[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
@@ -49,10 +174,92 @@ pub mod config {
             Self(value)
         }
     }
+
+    #[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+    #[serde(transparent)]
+    pub struct PropertyIterations(u64);
+
+    impl PropertyIterations {
+        pub fn new(num: u64) -> Self {
+            Self(num)
+        }
+
+        pub fn get(&self) -> u64 {
+            self.0
+        }
+    }
+
+    impl Default for PropertyIterations {
+        fn default() -> Self {
+            Self(100)
+        }
+    }
+
+    impl From<u64> for PropertyIterations {
+        fn from(value: u64) -> Self {
+            Self(value)
+        }
+    }
+
+    #[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+    #[serde(transparent)]
+    pub struct BuildJobs(usize);
+
+    impl BuildJobs {
+        pub fn new(num: usize) -> Self {
+            Self(num)
+        }
+
+        pub fn get(&self) -> usize {
+            self.0
+        }
+    }
+
+    impl Default for BuildJobs {
+        fn default() -> Self {
+            Self(1)
+        }
+    }
+
+    impl From<usize> for BuildJobs {
+        fn from(value: usize) -> Self {
+            Self(value)
+        }
+    }
+
+    #[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+    #[serde(transparent)]
+    pub struct BatchSize(usize);
+
+    impl BatchSize {
+        pub fn new(num: usize) -> Self {
+            Self(num)
+        }
+
+        pub fn get(&self) -> usize {
+            self.0
+        }
+    }
+
+    impl Default for BatchSize {
+        fn default() -> Self {
+            Self(1)
+        }
+    }
+
+    impl From<usize> for BatchSize {
+        fn from(value: usize) -> Self {
+            Self(value)
+        }
+    }
 }
 
 pub trait TestFileRunner {
     fn run(&self, test_file: String) -> impl Future<Output = TestFileReport> + Send;
+    fn run_batch(
+        &self,
+        test_files: Vec<String>,
+    ) -> impl Future<Output = Vec<TestFileReport>> + Send;
 }
 
 #[cfg(test)]
@@ -60,6 +267,10 @@ mockall::mock! {
     pub TestFileRunner {}
     impl TestFileRunner for TestFileRunner {
         fn run(&self, test_file: String) -> impl Future<Output = TestFileReport> + Send;
+        fn run_batch(
+            &self,
+            test_files: Vec<String>,
+        ) -> impl Future<Output = Vec<TestFileReport>> + Send;
     }
     impl Clone for TestFileRunner {
         fn clone(&self) -> Self {}
@@ -70,205 +281,1902 @@ mockall::mock! {
 pub struct NixTestRunner {
     nix_tests_path: String,
     timeout: u64,
+    seed: u32,
+    property_iterations: u64,
+    builder: NixBuilder,
+    overrides: Option<String>,
+    mocks: Vec<String>,
+    repl: Option<Arc<ReplPool>>,
+    limits: ResourceLimits,
+    profile: bool,
+    num_threads: usize,
 }
 
-impl NixTestRunner {
-    pub fn new(timeout: u64) -> Self {
-        let nix_tests_path = env::var("NIX_TESTS_LIB_PATH")
-            .expect("NIX_TESTS_LIB_PATH environment variable not set");
-
-        Self {
-            nix_tests_path,
-            timeout,
-        }
-    }
+enum EvaluationError {
+    Failed(String),
+    ResourceExceeded(ResourceLimit),
 }
 
-impl TestFileRunner for NixTestRunner {
-    async fn run(&self, test_file: String) -> TestFileReport {
-        let start = Instant::now();
-        let nix_tests = format!("import {} {{}}", self.nix_tests_path);
+/// Which tests of a file an evaluation runs.
+#[derive(Debug, Clone, Copy)]
+enum Selection<'a> {
+    All,
+    /// Just the tests at a path
+    Only(&'a [String]),
+    /// Every test, but only collecting the names of its checks
+    List,
+}
 
-        let errored = |error: String| {
-            TestFileReport::Errored(TestFileErroredReport {
-                file: test_file.clone(),
-                error,
-                elapsed: start.elapsed().as_millis(),
-            })
-        };
+/// Prefix of the messages traced by `mockImport` for each mock it sets up.
+const MOCK_TRACE: &str = "nix-tests mock: ";
 
-        let mut cmd = Command::new("nix-instantiate");
-        cmd.args(["--eval", "--strict", "--json", &test_file])
-            .args(["--arg", "nix-tests", &nix_tests])
-            .args(["-A", "tests"])
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped())
-            .kill_on_drop(true);
+/// Prefix of the report of each test, traced as it's run with `traceTests`.
+const TEST_TRACE: &str = "nix-tests test: ";
 
-        let output_future = cmd.output();
+/// A message traced by an evaluation and when it was printed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Trace {
+    pub message: String,
+    pub at: Instant,
+}
 
-        let output = if self.timeout > 0 {
-            let Ok(result) =
-                tokio::time::timeout(Duration::from_millis(self.timeout), output_future).await
-            else {
-                return TestFileReport::TimedOut(TestFileTimedOutReport {
-                    file: test_file,
-                    timeout: self.timeout,
-                    elapsed: start.elapsed().as_millis(),
-                });
-            };
-            result
-        } else {
-            output_future.await
-        };
+/// Collects the messages traced by an evaluation as they are printed, so
+/// they are still there when the evaluation times out.
+#[derive(Default)]
+pub struct Traces {
+    traces: Mutex<Vec<Trace>>,
+    /// Where the report of each test is sent as it's traced, for evaluations
+    /// that ask the library to trace them
+    tests: Option<mpsc::UnboundedSender<TestReport>>,
+}
 
-        let Ok(output) = output else {
-            return errored(format!(
-                "Failed to execute nix-instantiate: {}",
-                output.unwrap_err()
-            ));
-        };
+impl Traces {
+    fn sending_tests(tests: mpsc::UnboundedSender<TestReport>) -> Self {
+        Self {
+            traces: Mutex::default(),
+            tests: Some(tests),
+        }
+    }
 
-        if !output.status.success() {
-            return errored(String::from_utf8_lossy(&output.stderr).into_owned());
+    pub fn push(&self, message: &str) {
+        if let (Some(tests), Some(report)) = (&self.tests, message.strip_prefix(TEST_TRACE)) {
+            // Tests with build checks are sent once their builds are realised
+            if let Ok(report) = serde_json::from_str::<TestReport>(report) {
+                if !report.checks.iter().any(|check| check.build.is_some()) {
+                    let _ = tests.send(report);
+                }
+            }
+            return;
         }
 
-        let Ok(reports) = serde_json::from_slice::<Vec<TestReport>>(&output.stdout) else {
-            return errored(format!(
-                "Failed to deserialize test report: {}",
-                serde_json::from_slice::<Vec<TestReport>>(&output.stdout).unwrap_err()
-            ));
-        };
+        self.traces.lock().unwrap().push(Trace {
+            message: message.to_string(),
+            at: Instant::now(),
+        });
+    }
 
-        TestFileReport::Completed(TestFileCompletedReport {
-            file: test_file,
-            tests: reports,
-            elapsed: start.elapsed().as_millis(),
+    fn take(&self) -> Vec<Trace> {
+        std::mem::take(&mut self.traces.lock().unwrap())
+    }
+
+    fn messages(&self) -> Vec<String> {
+        self.take().into_iter().map(|trace| trace.message).collect()
+    }
+
+    /// The file of a batch being evaluated, and when its evaluation started.
+    fn current_batch_file(&self) -> Option<(String, Instant)> {
+        self.traces.lock().unwrap().iter().rev().find_map(|trace| {
+            let file = trace.message.strip_prefix(BATCH_FILE_TRACE)?;
+            Some((file.to_string(), trace.at))
         })
     }
 }
 
-pub struct TestSuiteRunner<TR: TestFileRunner, R: Reporter> {
-    pub test_runner: Arc<TR>,
-    pub reporter: R,
-    pub config: config::Config,
+struct Evaluation {
+    output: Result<Vec<u8>, EvaluationError>,
+    stats: Option<ResourceStats>,
 }
 
-impl<TR, R> TestSuiteRunner<TR, R>
-where
-    TR: TestFileRunner + Send + Sync + 'static,
-    R: Reporter + Send + Sync,
-{
-    pub fn new(test_runner: Arc<TR>, reporter: R, run_config: config::Config) -> Self {
+impl From<EvaluationError> for Evaluation {
+    fn from(error: EvaluationError) -> Self {
+        Err(error).into()
+    }
+}
+
+impl From<Result<Vec<u8>, EvaluationError>> for Evaluation {
+    fn from(output: Result<Vec<u8>, EvaluationError>) -> Self {
         Self {
-            test_runner,
-            reporter,
-            config: run_config,
+            output,
+            stats: None,
         }
     }
+}
 
-    pub async fn run(&self, test_files: &[TestFile]) -> TestSuiteReport {
-        let start = std::time::Instant::now();
+/// The test reports of a successfully evaluated file.
+struct EvaluatedTests {
+    tests: Vec<TestReport>,
+    stats: Option<ResourceStats>,
+    mocks: Vec<String>,
+}
 
-        let file_reports: Vec<TestFileReport> = stream::iter(test_files)
-            .filter_map(|tf| async move {
-                match tf {
-                    TestFile::Valid(path) => Some(path.clone()),
-                    TestFile::NotFound(path) => {
-                        self.report(&ReportEvent::TestFileNotFound(path.clone()));
-                        None
-                    }
-                    TestFile::Invalid(path) => {
-                        self.report(&ReportEvent::TestFileInvalid(path.clone()));
-                        None
-                    }
-                }
-            })
-            .map(|path| {
-                let runner = self.test_runner.clone();
-                async move { runner.run(path).await }
-            })
-            .buffer_unordered(self.config.num_threads.get())
-            .inspect(|report| {
-                self.report(&ReportEvent::TestFileCompleted(report.clone()));
-            })
-            .collect()
-            .await;
+/// Waits for a child process to exit and returns its resource usage, leaving
+/// it unreaped so its exit status can still be collected.
+#[cfg(target_os = "linux")]
+fn exited_process_usage(pid: u32) -> Option<libc::rusage> {
+    // SAFETY: both structs are plain data that the kernel fills in
+    let mut info: libc::siginfo_t = unsafe { std::mem::zeroed() };
+    let mut usage: libc::rusage = unsafe { std::mem::zeroed() };
+
+    // Unlike the libc wrapper, the waitid syscall also reports resource usage
+    let result = unsafe {
+        libc::syscall(
+            libc::SYS_waitid,
+            libc::P_PID,
+            pid,
+            &mut info as *mut libc::siginfo_t,
+            libc::WEXITED | libc::WNOWAIT,
+            &mut usage as *mut libc::rusage,
+        )
+    };
 
-        let elapsed = start.elapsed().as_millis();
-        let suite_report = TestSuiteReport::new(file_reports, elapsed);
+    (result == 0).then_some(usage)
+}
 
-        self.report(&ReportEvent::TestSuiteCompleted(suite_report.clone()));
+#[cfg(not(target_os = "linux"))]
+fn exited_process_usage(_pid: u32) -> Option<libc::rusage> {
+    None
+}
 
-        suite_report
-    }
+fn resource_stats(usage: &libc::rusage, nix: Option<NixStats>) -> ResourceStats {
+    let millis = |time: libc::timeval| time.tv_sec as u64 * 1000 + time.tv_usec as u64 / 1000;
 
-    fn report(&self, event: &ReportEvent) {
-        if let Some(message) = self.reporter.on(event) {
-            print!("{}", message);
-        }
+    ResourceStats {
+        // ru_maxrss is in KiB on Linux
+        peak_memory: usage.ru_maxrss as u64 * 1024,
+        user_time: millis(usage.ru_utime),
+        system_time: millis(usage.ru_stime),
+        nix,
     }
 }
 
-#[cfg(test)]
-mod test_suite_runner_tests {
-    use std::sync::Arc;
+/// A file Nix writes its evaluation statistics to, removed when dropped.
+struct NixStatsFile(PathBuf);
+
+impl NixStatsFile {
+    fn new() -> Self {
+        static COUNTER: AtomicU64 = AtomicU64::new(0);
+        let id = COUNTER.fetch_add(1, Ordering::Relaxed);
+        Self(env::temp_dir().join(format!(
+            "nix-tests-stats-{}-{}.json",
+            std::process::id(),
+            id
+        )))
+    }
 
-    use futures::FutureExt;
-    use mockall::{predicate::eq, Sequence};
+    fn read(&self) -> Option<NixStats> {
+        #[derive(Deserialize)]
+        #[serde(rename_all = "camelCase")]
+        struct RawStats {
+            #[serde(default)]
+            nr_thunks: u64,
+            #[serde(default)]
+            nr_function_calls: u64,
+            #[serde(default)]
+            nr_prim_op_calls: u64,
+            #[serde(default)]
+            gc: RawGcStats,
+        }
 
-    use super::*;
-    use crate::{
-        files::TestFile,
-        reports::{
-            MockReporter, ReportEvent, TestFileCompletedReport, TestFileReport, TestSuiteReport,
-        },
-        runners::config::Config,
-    };
+        #[derive(Deserialize, Default)]
+        #[serde(rename_all = "camelCase")]
+        struct RawGcStats {
+            #[serde(default)]
+            heap_size: u64,
+        }
 
-    #[tokio::test]
-    async fn it_runs_valid_tests() {
-        let mut test_runner = MockTestFileRunner::new();
-        test_runner
-            .expect_run()
-            .withf(|file| file == "my_test.nix")
-            .once()
-            .return_once(|_| {
-                async {
-                    TestFileReport::Completed(TestFileCompletedReport {
-                        file: "my_test.nix".to_string(),
-                        tests: vec![],
-                        elapsed: 0,
-                    })
-                }
-                .boxed()
-            });
+        let content = std::fs::read(&self.0).ok()?;
+        let stats = serde_json::from_slice::<RawStats>(&content).ok()?;
 
-        let mut sequence = Sequence::new();
+        Some(NixStats {
+            thunks: stats.nr_thunks,
+            function_calls: stats.nr_function_calls,
+            primop_calls: stats.nr_prim_op_calls,
+            gc_heap_size: stats.gc.heap_size,
+        })
+    }
+}
 
-        let mut reporter = MockReporter::new();
-        reporter
-            .expect_on()
-            .in_sequence(&mut sequence)
+impl Drop for NixStatsFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
+
+/// Resource limits applied to every spawned `nix-instantiate` process. The
+/// memory limit caps the address space rather than the memory in use.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ResourceLimits {
+    memory: Option<u64>,
+    cpu_time: Option<u64>,
+    open_files: Option<u64>,
+}
+
+#[cfg(all(target_os = "linux", target_env = "gnu"))]
+type RlimitResource = libc::__rlimit_resource_t;
+#[cfg(not(all(target_os = "linux", target_env = "gnu")))]
+type RlimitResource = libc::c_int;
+
+impl ResourceLimits {
+    pub fn new(config: &config::Config) -> Self {
+        Self {
+            memory: config.max_memory,
+            cpu_time: config.max_cpu_time,
+            open_files: config.max_open_files,
+        }
+    }
+
+    pub fn apply(&self, cmd: &mut Command) {
+        if *self == Self::default() {
+            return;
+        }
+        let limits = *self;
+        // SAFETY: the closure only calls getrlimit and setrlimit, which are
+        // async-signal-safe, between fork and exec
+        unsafe {
+            cmd.pre_exec(move || limits.set());
+        }
+    }
+
+    fn set(&self) -> std::io::Result<()> {
+        if let Some(max) = self.memory {
+            set_rlimit(libc::RLIMIT_AS, max.saturating_mul(1024 * 1024), None)?;
+        }
+        if let Some(max) = self.cpu_time {
+            // The soft limit sends SIGXCPU, the hard limit a second later SIGKILL
+            set_rlimit(libc::RLIMIT_CPU, max, Some(max.saturating_add(1)))?;
+        }
+        if let Some(max) = self.open_files {
+            set_rlimit(libc::RLIMIT_NOFILE, max, None)?;
+        }
+        Ok(())
+    }
+
+    /// Tells an evaluation that failed because of a limit from other failures.
+    /// Only the kernel sends SIGXCPU, but SIGKILL also comes from the OOM
+    /// killer or other processes, so it only counts once the CPU time is used.
+    fn classify(
+        &self,
+        error: String,
+        signal: Option<i32>,
+        stats: Option<&ResourceStats>,
+    ) -> EvaluationError {
+        if let Some(max) = self.cpu_time {
+            let cpu_time_used =
+                stats.is_some_and(|stats| stats.user_time + stats.system_time >= max * 1000);
+            if signal == Some(libc::SIGXCPU) || (signal == Some(libc::SIGKILL) && cpu_time_used) {
+                return EvaluationError::ResourceExceeded(ResourceLimit::CpuTime(max));
+            }
+        }
+        if let Some(max) = self.memory {
+            let out_of_memory = [
+                "out of memory",
+                "Out of Memory",
+                "bad_alloc",
+                "Cannot allocate memory",
+            ];
+            if out_of_memory.iter().any(|message| error.contains(message)) {
+                return EvaluationError::ResourceExceeded(ResourceLimit::Memory(max));
+            }
+        }
+        if let Some(max) = self.open_files {
+            if error.contains("Too many open files") {
+                return EvaluationError::ResourceExceeded(ResourceLimit::OpenFiles(max));
+            }
+        }
+        EvaluationError::Failed(error)
+    }
+}
+
+/// Lowers a resource limit, keeping the hard limit within the current one as
+/// only privileged processes may raise it.
+fn set_rlimit(resource: RlimitResource, soft: u64, hard: Option<u64>) -> std::io::Result<()> {
+    let mut limit = libc::rlimit {
+        rlim_cur: 0,
+        rlim_max: 0,
+    };
+    if unsafe { libc::getrlimit(resource, &mut limit) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+
+    let hard = (hard.unwrap_or(soft) as libc::rlim_t).min(limit.rlim_max);
+    limit.rlim_max = hard;
+    limit.rlim_cur = (soft as libc::rlim_t).min(hard);
+
+    if unsafe { libc::setrlimit(resource, &limit) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+impl NixTestRunner {
+    pub fn new(config: &config::Config) -> anyhow::Result<Self> {
+        let nix_tests_path = resolve_lib_path(config.lib_path.clone(), &cache_dir())?;
+
+        let mut runner = Self {
+            nix_tests_path,
+            timeout: config.timeout,
+            seed: config.seed.unwrap_or_default(),
+            property_iterations: config.property_iterations.get(),
+            builder: NixBuilder::new(config.build_timeout, config.build_jobs.get()),
+            overrides: (!config.overrides.is_empty()).then(|| overrides_json(&config.overrides)),
+            mocks: config.overrides.names(),
+            repl: None,
+            limits: ResourceLimits::new(config),
+            profile: config.profile,
+            num_threads: config.num_threads.get(),
+        };
+
+        if config.evaluator == config::Evaluator::Repl {
+            // Limits apply to whole processes, and repl processes are reused
+            // across test files
+            if runner.limits != ResourceLimits::default() {
+                bail!("max-memory, max-cpu-time and max-open-files can't be used with the repl evaluator");
+            }
+            // Tests are profiled by evaluating them on their own, which the
+            // repl processes aren't set up for
+            if runner.profile {
+                bail!("profile can't be used with the repl evaluator");
+            }
+            runner.repl = Some(Arc::new(ReplPool::new(
+                runner.repl_setup(),
+                config.num_threads.get(),
+            )));
+        }
+
+        Ok(runner)
+    }
+
+    /// Hashes everything besides the test file and its dependencies that
+    /// affects evaluation results.
+    pub fn fingerprint(&self) -> u64 {
+        let mut hasher = StableHasher::new();
+        nix_version().hash(&mut hasher);
+        lookup_path_roots().hash(&mut hasher);
+        std::fs::read(&self.nix_tests_path)
+            .unwrap_or_default()
+            .hash(&mut hasher);
+        self.property_iterations.hash(&mut hasher);
+        self.overrides.hash(&mut hasher);
+        self.repl.is_some().hash(&mut hasher);
+        self.profile.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    fn nix_tests_expr(&self, selection: Selection, trace_tests: bool) -> String {
+        let selection = match selection {
+            Selection::All => String::new(),
+            Selection::Only(path) => {
+                let path: Vec<String> = path
+                    .iter()
+                    .map(|name| format!("\"{}\"", nix_string(name)))
+                    .collect();
+                format!(" only = [ {} ];", path.join(" "))
+            }
+            Selection::List => " listOnly = true;".to_string(),
+        };
+        let trace_tests = if trace_tests {
+            " traceTests = true;"
+        } else {
+            ""
+        };
+
+        format!(
+            "import (/. + \"{}\") {{ seed = {}; propertyIterations = {};{}{} }}",
+            nix_string(&self.nix_tests_path),
+            self.seed,
+            self.property_iterations,
+            selection,
+            trace_tests
+        )
+    }
+
+    /// Lists the tests of a file and their checks, without running them.
+    pub async fn list_tests(&self, test_file: &str) -> Result<Vec<TestListing>, String> {
+        let output = match self
+            .instantiate_file(test_file, Selection::List, false, &Traces::default())
+            .await
+            .output
+        {
+            Ok(output) => output,
+            Err(EvaluationError::Failed(error)) => return Err(error),
+            Err(EvaluationError::ResourceExceeded(limit)) => {
+                return Err(format!("Exceeded {} limit", limit))
+            }
+        };
+
+        serde_json::from_slice(&output)
+            .map_err(|err| format!("Failed to deserialize test listing: {}", err))
+    }
+
+    fn repl_setup(&self) -> Vec<String> {
+        let mut setup = vec![
+            format!(
+                "__nixTests = {}",
+                self.nix_tests_expr(Selection::All, false)
+            ),
+            "__nixTestsPkgs = import <nixpkgs> { }".to_string(),
+        ];
+        if let Some(overrides) = &self.overrides {
+            setup.push(format!(
+                "__nixTestsOverrides = builtins.fromJSON \"{}\"",
+                nix_string(overrides)
+            ));
+        }
+        setup
+    }
+
+    async fn evaluate(
+        &self,
+        test_file: &str,
+        selection: Selection<'_>,
+        read_write: bool,
+        traces: &Traces,
+    ) -> Evaluation {
+        match (&self.repl, selection) {
+            (Some(repl), Selection::All) if traces.tests.is_none() => {
+                let overrides = self.overrides.as_ref().map(|_| "__nixTestsOverrides");
+                let expr =
+                    test_file_expr(test_file, "__nixTests", overrides, Some("__nixTestsPkgs"));
+                repl.evaluate(&format!("builtins.toJSON ({}).tests", expr), traces)
+                    .await
+                    .map_err(EvaluationError::Failed)
+                    .into()
+            }
+            // The repl processes are set up to run every test of a file,
+            // without tracing them
+            (_, selection) => {
+                self.instantiate_file(test_file, selection, read_write, traces)
+                    .await
+            }
+        }
+    }
+
+    async fn instantiate_file(
+        &self,
+        test_file: &str,
+        selection: Selection<'_>,
+        read_write: bool,
+        traces: &Traces,
+    ) -> Evaluation {
+        match &self.overrides {
+            Some(_) => {
+                let expr = test_file_expr(
+                    test_file,
+                    "nix-tests",
+                    Some("(builtins.fromJSON overrides)"),
+                    None,
+                );
+                self.instantiate(
+                    &[
+                        "--expr",
+                        &format!("{{ nix-tests, overrides }}: {}", expr),
+                        "-A",
+                        "tests",
+                    ],
+                    selection,
+                    read_write,
+                    traces,
+                )
+                .await
+            }
+            None => {
+                self.instantiate(&[test_file, "-A", "tests"], selection, read_write, traces)
+                    .await
+            }
+        }
+    }
+
+    /// Measures the evaluation time of each test by evaluating them one at a
+    /// time, less the time taken to evaluate the file without any tests.
+    async fn profile_tests(&self, test_file: &str, tests: &mut [TestReport]) {
+        let baseline = self.time_evaluation(test_file, &[]).await.unwrap_or(0);
+
+        let mut paths: Vec<Vec<String>> = tests.iter().map(test_path).collect();
+        paths.dedup();
+
+        for path in paths {
+            let elapsed = self
+                .time_evaluation(test_file, &path)
+                .await
+                .map(|elapsed| elapsed.saturating_sub(baseline));
+
+            for test in tests.iter_mut().filter(|test| test_path(test) == path) {
+                test.elapsed = elapsed;
+            }
+        }
+    }
+
+    async fn time_evaluation(&self, test_file: &str, only: &[String]) -> Option<u128> {
+        let start = Instant::now();
+        let traces = Traces::default();
+        let evaluation = self.instantiate_file(test_file, Selection::Only(only), false, &traces);
+
+        let evaluation = if self.timeout > 0 {
+            tokio::time::timeout(Duration::from_millis(self.timeout), evaluation)
+                .await
+                .ok()?
+        } else {
+            evaluation.await
+        };
+
+        evaluation.output.ok().map(|_| start.elapsed().as_millis())
+    }
+
+    async fn evaluate_batch(&self, test_files: &[String], traces: &Traces) -> Evaluation {
+        match &self.repl {
+            Some(repl) => {
+                let overrides = self.overrides.as_ref().map(|_| "__nixTestsOverrides");
+                let expr = batch_expr(test_files, "__nixTests", overrides, Some("__nixTestsPkgs"));
+                repl.evaluate(&format!("builtins.toJSON ({})", expr), traces)
+                    .await
+                    .map_err(EvaluationError::Failed)
+                    .into()
+            }
+            None => {
+                let overrides = self
+                    .overrides
+                    .as_ref()
+                    .map(|_| "(builtins.fromJSON overrides)");
+                let expr = batch_expr(test_files, "nix-tests", overrides, None);
+                self.instantiate(
+                    &[
+                        "--expr",
+                        &format!("{{ nix-tests, overrides ? null }}: {}", expr),
+                    ],
+                    Selection::All,
+                    false,
+                    traces,
+                )
+                .await
+            }
+        }
+    }
+
+    /// Evaluates with nix-instantiate, which only writes derivations to the
+    /// store in read-write mode.
+    async fn instantiate(
+        &self,
+        args: &[&str],
+        selection: Selection<'_>,
+        read_write: bool,
+        traces: &Traces,
+    ) -> Evaluation {
+        let stats_file = NixStatsFile::new();
+
+        let mut cmd = Command::new("nix-instantiate");
+        cmd.args(["--eval", "--strict", "--json"]);
+        if read_write {
+            cmd.arg("--read-write-mode");
+        }
+        cmd.args(args);
+        if let Some(overrides) = &self.overrides {
+            cmd.args(["--argstr", "overrides", overrides]);
+        }
+        cmd.args([
+            "--arg",
+            "nix-tests",
+            &self.nix_tests_expr(selection, traces.tests.is_some()),
+        ])
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .env("NIX_SHOW_STATS", "1")
+        .env("NIX_SHOW_STATS_PATH", &stats_file.0)
+        .kill_on_drop(true);
+        self.limits.apply(&mut cmd);
+
+        let failed = |err: std::io::Error| {
+            Evaluation::from(EvaluationError::Failed(format!(
+                "Failed to execute nix-instantiate: {}",
+                err
+            )))
+        };
+
+        let mut child = match cmd.spawn() {
+            Ok(child) => child,
+            Err(err) => return failed(err),
+        };
+
+        let (mut stdout, mut stderr) = (Vec::new(), Vec::new());
+        let (Some(mut child_stdout), Some(child_stderr)) =
+            (child.stdout.take(), child.stderr.take())
+        else {
+            unreachable!("stdout and stderr are piped");
+        };
+        // Traces are read line by line, as they are printed
+        let read_stderr = async {
+            let mut child_stderr = BufReader::new(child_stderr);
+            let mut line = Vec::new();
+            while child_stderr.read_until(b'\n', &mut line).await? > 0 {
+                if let Some(message) = String::from_utf8_lossy(&line)
+                    .trim_end()
+                    .strip_prefix("trace: ")
+                {
+                    traces.push(message);
+                }
+                stderr.append(&mut line);
+            }
+            Ok(())
+        };
+        let read = tokio::try_join!(child_stdout.read_to_end(&mut stdout), read_stderr);
+        if let Err(err) = read {
+            return failed(err);
+        }
+
+        let usage = match child.id() {
+            Some(pid) => tokio::task::spawn_blocking(move || exited_process_usage(pid))
+                .await
+                .ok()
+                .flatten(),
+            None => None,
+        };
+
+        let status = match child.wait().await {
+            Ok(status) => status,
+            Err(err) => return failed(err),
+        };
+
+        let stats = usage.map(|usage| resource_stats(&usage, stats_file.read()));
+
+        let output = if status.success() {
+            Ok(stdout)
+        } else {
+            Err(self.limits.classify(
+                String::from_utf8_lossy(&stderr).into_owned(),
+                status.signal(),
+                stats.as_ref(),
+            ))
+        };
+
+        Evaluation { output, stats }
+    }
+
+    async fn realise_builds(&self, mut tests: Vec<TestReport>) -> Vec<TestReport> {
+        let builds = tests
+            .iter_mut()
+            .flat_map(|test| test.checks.iter_mut())
+            .filter_map(|check| {
+                let drv_path = check.build.as_ref()?.drv_path.clone();
+                Some(async move {
+                    let result = self.builder.build(&drv_path).await;
+                    apply_build_result(check, result);
+                })
+            });
+
+        future::join_all(builds).await;
+
+        for test in tests.iter_mut() {
+            test.success = test.checks.iter().all(|check| check.success);
+        }
+
+        tests
+    }
+}
+
+#[derive(Clone)]
+struct NixBuilder {
+    semaphore: Arc<Semaphore>,
+    timeout: u64,
+}
+
+impl NixBuilder {
+    fn new(timeout: u64, jobs: usize) -> Self {
+        Self {
+            semaphore: Arc::new(Semaphore::new(jobs.max(1))),
+            timeout,
+        }
+    }
+
+    async fn build(&self, drv_path: &str) -> Result<String, String> {
+        let _permit = self
+            .semaphore
+            .acquire()
+            .await
+            .map_err(|err| format!("Failed to schedule build: {err}"))?;
+
+        let mut cmd = Command::new("nix-build");
+        cmd.args(["--no-out-link", drv_path])
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true);
+
+        let output_future = cmd.output();
+
+        let output = if self.timeout > 0 {
+            tokio::time::timeout(Duration::from_millis(self.timeout), output_future)
+                .await
+                .map_err(|_| format!("Build timed out after {}ms", self.timeout))?
+        } else {
+            output_future.await
+        };
+
+        let output = output.map_err(|err| format!("Failed to execute nix-build: {err}"))?;
+
+        if !output.status.success() {
+            return Err(format!(
+                "Build failed:\n{}",
+                String::from_utf8_lossy(&output.stderr).trim_end()
+            ));
+        }
+
+        String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .next()
+            .map(str::to_string)
+            .ok_or_else(|| "Build produced no output path".to_string())
+    }
+}
+
+/// Whether any build check refers to a derivation that isn't in the store,
+/// as happens when the test file was evaluated in read-only mode.
+fn has_unwritten_derivations(tests: &[TestReport]) -> bool {
+    tests
+        .iter()
+        .flat_map(|test| &test.checks)
+        .filter_map(|check| check.build.as_ref())
+        .any(|build| !Path::new(&build.drv_path).exists())
+}
+
+fn apply_build_result(check: &mut CheckReport, result: Result<String, String>) {
+    let Some(build) = check.build.as_mut() else {
+        return;
+    };
+
+    match result {
+        Ok(output) => {
+            if let Some(expected) = &build.output_contains {
+                if !Path::new(&output).join(expected).exists() {
+                    check.success = false;
+                    check.failure = Some(format!(
+                        "Expected: '{expected}' to exist in build output\nGot: {output}"
+                    ));
+                }
+            }
+            build.output = Some(output);
+        }
+        Err(error) => {
+            check.success = false;
+            check.failure = Some(error);
+        }
+    }
+}
+
+impl NixTestRunner {
+    /// Runs the tests at a path of a test file, or every test for an empty
+    /// path, sending the report of each test as soon as it's run. When the
+    /// evaluation fails, the tests sent before are the ones that completed.
+    pub async fn run_streamed(
+        &self,
+        test_file: String,
+        path: &[String],
+        completed: mpsc::UnboundedSender<TestReport>,
+    ) -> TestFileReport {
+        let selection = match path {
+            [] => Selection::All,
+            path => Selection::Only(path),
+        };
+        self.run_selected(test_file, selection, Some(completed))
+            .await
+    }
+
+    async fn evaluate_tests(
+        &self,
+        test_file: &str,
+        selection: Selection<'_>,
+        read_write: bool,
+        traces: Traces,
+        start: Instant,
+    ) -> Result<EvaluatedTests, TestFileReport> {
+        let evaluation = self.evaluate(test_file, selection, read_write, &traces);
+
+        let Evaluation { output, stats } = if self.timeout > 0 {
+            let Ok(result) =
+                tokio::time::timeout(Duration::from_millis(self.timeout), evaluation).await
+            else {
+                return Err(TestFileReport::TimedOut(TestFileTimedOutReport {
+                    file: test_file.to_string(),
+                    timeout: self.timeout,
+                    elapsed: start.elapsed().as_millis(),
+                }));
+            };
+            result
+        } else {
+            evaluation.await
+        };
+
+        let errored = |error: String, stats: Option<ResourceStats>| {
+            TestFileReport::Errored(TestFileErroredReport {
+                file: test_file.to_string(),
+                error,
+                elapsed: start.elapsed().as_millis(),
+                stats,
+            })
+        };
+
+        let output = match output {
+            Ok(output) => output,
+            Err(EvaluationError::Failed(error)) => return Err(errored(error, stats)),
+            Err(EvaluationError::ResourceExceeded(limit)) => {
+                return Err(TestFileReport::ResourceExceeded(
+                    TestFileResourceExceededReport {
+                        file: test_file.to_string(),
+                        limit,
+                        elapsed: start.elapsed().as_millis(),
+                        stats,
+                    },
+                ))
+            }
+        };
+
+        let Ok(reports) = serde_json::from_slice::<Vec<TestReport>>(&output) else {
+            return Err(errored(
+                format!(
+                    "Failed to deserialize test report: {}",
+                    serde_json::from_slice::<Vec<TestReport>>(&output).unwrap_err()
+                ),
+                stats,
+            ));
+        };
+
+        Ok(EvaluatedTests {
+            tests: reports,
+            stats,
+            mocks: self.active_mocks(&traces.messages()),
+        })
+    }
+
+    /// The mocks from the runner's overrides, followed by those that the
+    /// test file set up with `mockImport`.
+    fn active_mocks(&self, traces: &[String]) -> Vec<String> {
+        let mut mocks = self.mocks.clone();
+        for mock in traces
+            .iter()
+            .filter_map(|trace| trace.strip_prefix(MOCK_TRACE))
+        {
+            if !mocks.iter().any(|active| active == mock) {
+                mocks.push(mock.to_string());
+            }
+        }
+        mocks
+    }
+
+    async fn run_selected(
+        &self,
+        test_file: String,
+        selection: Selection<'_>,
+        completed: Option<mpsc::UnboundedSender<TestReport>>,
+    ) -> TestFileReport {
+        let start = Instant::now();
+
+        let traces = completed
+            .clone()
+            .map(Traces::sending_tests)
+            .unwrap_or_default();
+        let mut evaluated = self
+            .evaluate_tests(&test_file, selection, false, traces, start)
+            .await;
+        // Derivations are only written to the store in read-write mode, which
+        // is left off unless the file turns out to have build checks to realise.
+        // The tests without any were already sent by the first evaluation
+        if matches!(&evaluated, Ok(evaluated) if has_unwritten_derivations(&evaluated.tests)) {
+            evaluated = self
+                .evaluate_tests(&test_file, selection, true, Traces::default(), start)
+                .await;
+        }
+        let EvaluatedTests {
+            tests,
+            stats,
+            mocks,
+        } = match evaluated {
+            Ok(evaluated) => evaluated,
+            Err(report) => return report,
+        };
+
+        let mut reports = self.realise_builds(tests).await;
+
+        if self.profile {
+            self.profile_tests(&test_file, &mut reports).await;
+        }
+
+        if let Some(completed) = completed {
+            for report in reports
+                .iter()
+                .filter(|report| report.checks.iter().any(|check| check.build.is_some()))
+            {
+                let _ = completed.send(report.clone());
+            }
+        }
+
+        TestFileReport::Completed(TestFileCompletedReport {
+            file: test_file,
+            tests: reports,
+            elapsed: start.elapsed().as_millis(),
+            mocks,
+            cached: false,
+            stats,
+        })
+    }
+}
+
+impl TestFileRunner for NixTestRunner {
+    async fn run(&self, test_file: String) -> TestFileReport {
+        self.run_selected(test_file, Selection::All, None).await
+    }
+
+    async fn run_batch(&self, test_files: Vec<String>) -> Vec<TestFileReport> {
+        if test_files.len() == 1 {
+            return vec![self.run(test_files[0].clone()).await];
+        }
+
+        let start = Instant::now();
+        let traces = Traces::default();
+
+        let evaluation = self.evaluate_batch(&test_files, &traces);
+
+        // Every file of the batch has the timeout from when its evaluation
+        // started, as traced before evaluating it
+        let (evaluation, timed_out) = if self.timeout > 0 {
+            let timeout = Duration::from_millis(self.timeout);
+            tokio::pin!(evaluation);
+            loop {
+                let current = traces.current_batch_file();
+                let started = current.as_ref().map_or(start, |(_, at)| *at);
+                tokio::select! {
+                    evaluation = &mut evaluation => break (Some(evaluation), None),
+                    _ = tokio::time::sleep_until(started + timeout) => {
+                        if traces.current_batch_file() == current {
+                            break (None, current);
+                        }
+                    }
+                }
+            }
+        } else {
+            (Some(evaluation.await), None)
+        };
+        let end = Instant::now();
+
+        let mut entries = evaluation
+            .and_then(|evaluation| evaluation.output.ok())
+            .and_then(|output| serde_json::from_slice::<BTreeMap<String, BatchEntry>>(&output).ok())
+            .unwrap_or_default();
+        let mut traces = batch_traces(traces.take(), end);
+
+        let mut reports = Vec::with_capacity(test_files.len());
+        let mut reruns = Vec::new();
+        for test_file in test_files {
+            if let Some((_, started)) = timed_out.as_ref().filter(|(file, _)| *file == test_file) {
+                reports.push(Some(TestFileReport::TimedOut(TestFileTimedOutReport {
+                    file: test_file,
+                    timeout: self.timeout,
+                    elapsed: end.duration_since(*started).as_millis(),
+                })));
+                continue;
+            }
+
+            // When the batch failed as a whole, the files evaluated before
+            // still have their traced results
+            let file_traces = traces.remove(&test_file).unwrap_or_default();
+            let entry = entries.remove(&test_file).or(file_traces.entry);
+
+            // Files that failed within the batch, or weren't evaluated before
+            // it failed, are evaluated on their own to report the actual error.
+            // So are files with build checks, to write their derivations.
+            let Some(tests) = entry
+                .and_then(|entry| entry.tests)
+                .filter(|tests| !has_unwritten_derivations(tests))
+            else {
+                reports.push(None);
+                reruns.push(test_file);
+                continue;
+            };
+
+            let builds_start = Instant::now();
+            let tests = self.realise_builds(tests).await;
+            reports.push(Some(TestFileReport::Completed(TestFileCompletedReport {
+                file: test_file,
+                tests,
+                elapsed: file_traces.elapsed + builds_start.elapsed().as_millis(),
+                mocks: self.active_mocks(&file_traces.messages),
+                cached: false,
+                stats: None,
+            })));
+        }
+
+        let mut reruns = stream::iter(reruns)
+            .map(|test_file| self.run(test_file))
+            .buffered(self.num_threads);
+        let mut ordered = Vec::with_capacity(reports.len());
+        for report in reports {
+            ordered.push(match report {
+                Some(report) => report,
+                None => reruns
+                    .next()
+                    .await
+                    .expect("every file left out of the batch is run on its own"),
+            });
+        }
+
+        ordered
+    }
+}
+
+/// FNV-1a, whose output unlike `DefaultHasher`'s stays the same across Rust
+/// releases, so cached reports survive a toolchain upgrade.
+struct StableHasher(u64);
+
+impl StableHasher {
+    fn new() -> Self {
+        Self(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl Hasher for StableHasher {
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= u64::from(*byte);
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+/// Stores completed test file reports on disk, keyed by the contents of the
+/// test file and its dependencies, and the environment variables they read.
+pub struct ResultCache {
+    dir: PathBuf,
+    fingerprint: u64,
+    /// The configured seed, without which results of property tests aren't
+    /// cached, as every run tries a new one
+    seed: Option<u32>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CacheEntry {
+    tests: Vec<TestReport>,
+    elapsed: u128,
+    mocks: Vec<String>,
+}
+
+impl ResultCache {
+    pub fn new(dir: PathBuf, fingerprint: impl Hash) -> Self {
+        let mut hasher = StableHasher::new();
+        fingerprint.hash(&mut hasher);
+        Self {
+            dir,
+            fingerprint: hasher.finish(),
+            seed: None,
+        }
+    }
+
+    pub fn with_seed(self, seed: Option<u32>) -> Self {
+        Self { seed, ..self }
+    }
+
+    /// Where the report of a test file is stored, or `None` when it can't be
+    /// cached because its dependencies aren't all known statically, or it has
+    /// property tests and the seed is random.
+    fn entry_path(&self, test_file: &str) -> Option<PathBuf> {
+        let dependencies = dependencies(Path::new(test_file));
+        let env = dependencies.env.filter(|_| dependencies.complete)?;
+
+        let mut hasher = StableHasher::new();
+        self.fingerprint.hash(&mut hasher);
+        if dependencies.properties {
+            self.seed?.hash(&mut hasher);
+        }
+        for dependency in dependencies.files {
+            dependency.hash(&mut hasher);
+            std::fs::read(&dependency)
+                .unwrap_or_default()
+                .hash(&mut hasher);
+        }
+        for name in env {
+            env::var_os(&name).hash(&mut hasher);
+            name.hash(&mut hasher);
+        }
+        Some(self.dir.join(format!("{:016x}.json", hasher.finish())))
+    }
+
+    fn get(&self, entry_path: &Path, test_file: &str) -> Option<TestFileReport> {
+        let content = std::fs::read(entry_path).ok()?;
+        let entry = serde_json::from_slice::<CacheEntry>(&content).ok()?;
+
+        Some(TestFileReport::Completed(TestFileCompletedReport {
+            file: test_file.to_string(),
+            tests: entry.tests,
+            elapsed: entry.elapsed,
+            mocks: entry.mocks,
+            cached: true,
+            stats: None,
+        }))
+    }
+
+    fn put(&self, entry_path: &Path, report: &TestFileReport) {
+        let TestFileReport::Completed(report) = report else {
+            return;
+        };
+        // Builds depend on the store rather than on the files that are hashed
+        let has_builds = report
+            .tests
+            .iter()
+            .flat_map(|test| &test.checks)
+            .any(|check| check.build.is_some());
+        if has_builds {
+            return;
+        }
+
+        let entry = CacheEntry {
+            tests: report.tests.clone(),
+            elapsed: report.elapsed,
+            mocks: report.mocks.clone(),
+        };
+        let Ok(content) = serde_json::to_vec(&entry) else {
+            return;
+        };
+
+        // The cache is best effort, a failed write only costs a re-evaluation
+        let tmp_path = entry_path.with_extension(format!("{}.tmp", std::process::id()));
+        let _ = std::fs::create_dir_all(&self.dir)
+            .and_then(|_| std::fs::write(&tmp_path, content))
+            .and_then(|_| std::fs::rename(&tmp_path, entry_path));
+    }
+}
+
+/// Serves test file reports from a [`ResultCache`] when one is given, and
+/// runs the remaining files with the wrapped runner.
+pub struct CachingRunner<TR: TestFileRunner> {
+    inner: TR,
+    cache: Option<ResultCache>,
+}
+
+impl<TR: TestFileRunner> CachingRunner<TR> {
+    pub fn new(inner: TR, cache: Option<ResultCache>) -> Self {
+        Self { inner, cache }
+    }
+}
+
+impl<TR: TestFileRunner + Sync> TestFileRunner for CachingRunner<TR> {
+    async fn run(&self, test_file: String) -> TestFileReport {
+        let Some((cache, entry_path)) = self
+            .cache
+            .as_ref()
+            .and_then(|cache| Some((cache, cache.entry_path(&test_file)?)))
+        else {
+            return self.inner.run(test_file).await;
+        };
+
+        if let Some(report) = cache.get(&entry_path, &test_file) {
+            return report;
+        }
+
+        let report = self.inner.run(test_file).await;
+        cache.put(&entry_path, &report);
+        report
+    }
+
+    async fn run_batch(&self, test_files: Vec<String>) -> Vec<TestFileReport> {
+        let Some(cache) = &self.cache else {
+            return self.inner.run_batch(test_files).await;
+        };
+
+        let entry_paths: Vec<Option<PathBuf>> = test_files
+            .iter()
+            .map(|file| cache.entry_path(file))
+            .collect();
+        let cached: Vec<Option<TestFileReport>> = test_files
+            .iter()
+            .zip(&entry_paths)
+            .map(|(file, entry_path)| cache.get(entry_path.as_deref()?, file))
+            .collect();
+
+        let uncached: Vec<String> = test_files
+            .iter()
+            .zip(&cached)
+            .filter(|(_, report)| report.is_none())
+            .map(|(file, _)| file.clone())
+            .collect();
+
+        let mut evaluated = match uncached.len() {
+            0 => vec![],
+            1 => vec![self.inner.run(uncached[0].clone()).await],
+            _ => self.inner.run_batch(uncached).await,
+        }
+        .into_iter();
+
+        cached
+            .into_iter()
+            .zip(entry_paths)
+            .filter_map(|(report, entry_path)| {
+                report.or_else(|| {
+                    let report = evaluated.next()?;
+                    if let Some(entry_path) = entry_path {
+                        cache.put(&entry_path, &report);
+                    }
+                    Some(report)
+                })
+            })
+            .collect()
+    }
+}
+
+fn nix_version() -> Option<Vec<u8>> {
+    std::process::Command::new("nix-instantiate")
+        .arg("--version")
+        .output()
+        .ok()
+        .map(|output| output.stdout)
+}
+
+/// Resolves the entries of the Nix search path, such as `<nixpkgs>`, to the
+/// store paths they currently point to.
+fn lookup_path_roots() -> Vec<(String, PathBuf)> {
+    #[derive(Deserialize)]
+    struct Entry {
+        prefix: String,
+        path: String,
+    }
+
+    let Ok(output) = std::process::Command::new("nix-instantiate")
+        .args(["--eval", "--json", "--expr", "builtins.nixPath"])
+        .output()
+    else {
+        return vec![];
+    };
+
+    serde_json::from_slice::<Vec<Entry>>(&output.stdout)
+        .unwrap_or_default()
+        .into_iter()
+        .map(|entry| {
+            let path = Path::new(&entry.path);
+            let root = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+            (entry.prefix, root)
+        })
+        .collect()
+}
+
+const NIX_TESTS_LIB: &str = include_str!("../lib/tests.nix");
+
+fn resolve_lib_path(lib_path: Option<String>, cache_dir: &Path) -> anyhow::Result<String> {
+    let Some(lib_path) = lib_path.or_else(|| env::var("NIX_TESTS_LIB_PATH").ok()) else {
+        let path = materialise_lib(cache_dir)?;
+        return Ok(path.to_string_lossy().into_owned());
+    };
+
+    if !Path::new(&lib_path).is_file() {
+        bail!("Nix library not found at '{lib_path}'");
+    }
+
+    Ok(absolute_path(&lib_path))
+}
+
+fn materialise_lib(dir: &Path) -> anyhow::Result<PathBuf> {
+    let mut hasher = StableHasher::new();
+    NIX_TESTS_LIB.hash(&mut hasher);
+    let path = dir.join(format!("tests-{:016x}.nix", hasher.finish()));
+
+    if path.is_file() {
+        return Ok(path);
+    }
+
+    std::fs::create_dir_all(dir)
+        .context(format!("Failed to create directory: {}", dir.display()))?;
+
+    let tmp_path = path.with_extension(format!("{}.tmp", std::process::id()));
+    std::fs::write(&tmp_path, NIX_TESTS_LIB).context(format!(
+        "Failed to write Nix library: {}",
+        tmp_path.display()
+    ))?;
+    std::fs::rename(&tmp_path, &path)
+        .context(format!("Failed to write Nix library: {}", path.display()))?;
+
+    Ok(path)
+}
+
+fn overrides_json(overrides: &config::Overrides) -> String {
+    let imports = overrides
+        .imports
+        .iter()
+        .map(|(path, replacement)| (absolute_path(path), absolute_path(replacement)))
+        .collect::<BTreeMap<_, _>>();
+
+    serde_json::json!({
+        "env": overrides.env,
+        "builtins": overrides.builtins,
+        "imports": imports,
+    })
+    .to_string()
+}
+
+/// The path of the test a report belongs to, without the case label.
+fn test_path(test: &TestReport) -> Vec<String> {
+    match test.case {
+        Some(_) => test.path[..test.path.len().saturating_sub(1)].to_vec(),
+        None => test.path.clone(),
+    }
+}
+
+#[derive(Debug, Deserialize, PartialEq, Eq)]
+struct BatchEntry {
+    #[serde(default)]
+    tests: Option<Vec<TestReport>>,
+}
+
+/// Prefix of the message traced before evaluating each file of a batch, which
+/// attributes the messages traced after it to that file.
+const BATCH_FILE_TRACE: &str = "nix-tests file: ";
+
+/// Prefix of the entry of each file of a batch, traced once it's evaluated so
+/// it's kept when a later file fails the whole batch.
+const BATCH_RESULT_TRACE: &str = "nix-tests result: ";
+
+/// The messages traced while evaluating one file of a batch, how long its
+/// evaluation took, and its entry if the evaluation finished.
+#[derive(Debug, Default, PartialEq, Eq)]
+struct BatchFileTraces {
+    messages: Vec<String>,
+    elapsed: u128,
+    entry: Option<BatchEntry>,
+}
+
+/// Groups the messages traced while evaluating a batch by test file, timing
+/// each file from its own trace to the next file's, or to the batch's `end`.
+fn batch_traces(traces: Vec<Trace>, end: Instant) -> BTreeMap<String, BatchFileTraces> {
+    let mut grouped: BTreeMap<String, BatchFileTraces> = BTreeMap::new();
+    let mut current: Option<(String, Instant)> = None;
+
+    for trace in traces {
+        if let Some(file) = trace.message.strip_prefix(BATCH_FILE_TRACE) {
+            if let Some((previous, started)) = current.take() {
+                grouped.entry(previous).or_default().elapsed =
+                    trace.at.duration_since(started).as_millis();
+            }
+            current = Some((file.to_string(), trace.at));
+        } else if let Some((file, _)) = &current {
+            if let Some(entry) = trace.message.strip_prefix(BATCH_RESULT_TRACE) {
+                grouped.entry(file.clone()).or_default().entry = serde_json::from_str(entry).ok();
+                continue;
+            }
+            grouped
+                .entry(file.clone())
+                .or_default()
+                .messages
+                .push(trace.message);
+        }
+    }
+    if let Some((file, started)) = current {
+        grouped.entry(file).or_default().elapsed = end.duration_since(started).as_millis();
+    }
+
+    grouped
+}
+
+fn batch_expr(
+    test_files: &[String],
+    nix_tests: &str,
+    overrides: Option<&str>,
+    pkgs: Option<&str>,
+) -> String {
+    let entries: String = test_files
+        .iter()
+        .map(|test_file| {
+            format!(
+                " \"{file}\" = builtins.trace \"{BATCH_FILE_TRACE}{file}\" \
+                (let result = builtins.tryEval (let tests = ({}).tests; in builtins.deepSeq tests tests); \
+                entry = if result.success then {{ tests = result.value; }} else {{ error = true; }}; \
+                in builtins.trace \"{BATCH_RESULT_TRACE}${{builtins.toJSON entry}}\" entry);",
+                test_file_expr(test_file, nix_tests, overrides, pkgs),
+                file = nix_string(test_file),
+            )
+        })
+        .collect();
+
+    format!("{{{} }}", entries)
+}
+
+fn test_file_expr(
+    test_file: &str,
+    nix_tests: &str,
+    overrides: Option<&str>,
+    pkgs: Option<&str>,
+) -> String {
+    let path = format!("(/. + \"{}\")", nix_string(&absolute_path(test_file)));
+    let import = match overrides {
+        // The runner's overrides are reported from the config, so they are
+        // applied without tracing them as `mockImport` does
+        Some(overrides) => format!("scopedImport ({nix_tests}.mockScope {overrides}) {path}"),
+        None => format!("import {path}"),
+    };
+    let pkgs = pkgs
+        .map(|pkgs| format!(" pkgs = {pkgs};"))
+        .unwrap_or_default();
+
+    format!(
+        "let testFile = {import}; in \
+        if builtins.isFunction testFile \
+        then testFile (builtins.intersectAttrs (builtins.functionArgs testFile) {{ nix-tests = {nix_tests};{pkgs} }}) \
+        else testFile"
+    )
+}
+
+fn absolute_path(path: &str) -> String {
+    std::path::absolute(path)
+        .map(|path| path.to_string_lossy().into_owned())
+        .unwrap_or_else(|_| path.to_string())
+}
+
+fn nix_string(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace("${", "\\${")
+}
+
+/// Elapsed times of test files from previous runs, keyed by absolute path.
+#[derive(Default, Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(transparent)]
+pub struct Timings(BTreeMap<String, u128>);
+
+impl Timings {
+    /// Returns where the timings of a project are kept, so projects sharing a
+    /// cache directory don't mix up their timings.
+    pub fn path(cache_dir: &Path, project_root: &Path) -> PathBuf {
+        let mut hasher = StableHasher::new();
+        project_root.hash(&mut hasher);
+        cache_dir
+            .join("timings")
+            .join(format!("{:016x}.json", hasher.finish()))
+    }
+
+    pub fn load(path: &Path) -> Self {
+        std::fs::read(path)
+            .ok()
+            .and_then(|content| serde_json::from_slice(&content).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)
+                .context(format!("Failed to create directory: {}", dir.display()))?;
+        }
+        let tmp_path = path.with_extension(format!("{}.tmp", std::process::id()));
+        std::fs::write(&tmp_path, serde_json::to_vec(self)?)
+            .context(format!("Failed to write timings: {}", tmp_path.display()))?;
+        std::fs::rename(&tmp_path, path)
+            .context(format!("Failed to write timings: {}", path.display()))?;
+        Ok(())
+    }
+
+    pub fn get(&self, test_file: &str) -> Option<u128> {
+        self.0.get(&absolute_path(test_file)).copied()
+    }
+
+    /// Records the elapsed times of evaluated test files, skipping cached ones.
+    pub fn record(&mut self, reports: &[TestFileReport]) {
+        for report in reports {
+            if let TestFileReport::Completed(TestFileCompletedReport { cached: true, .. }) = report
+            {
+                continue;
+            }
+            self.0
+                .insert(absolute_path(report.file()), report.elapsed());
+        }
+    }
+}
+
+/// Shuffles items in place with a SplitMix64 generator, so the same seed
+/// always gives the same order.
+fn shuffle<T>(items: &mut [T], seed: u32) {
+    let mut state = seed as u64;
+    let mut next = || {
+        state = state.wrapping_add(0x9E3779B97F4A7C15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+        z ^ (z >> 31)
+    };
+
+    for i in (1..items.len()).rev() {
+        let j = (next() % (i as u64 + 1)) as usize;
+        items.swap(i, j);
+    }
+}
+
+pub struct TestSuiteRunner<TR: TestFileRunner, R: Reporter> {
+    pub test_runner: Arc<TR>,
+    pub reporter: R,
+    pub config: config::Config,
+    pub timings: Timings,
+    pub shard: Option<Shard>,
+}
+
+impl<TR, R> TestSuiteRunner<TR, R>
+where
+    TR: TestFileRunner + Send + Sync + 'static,
+    R: Reporter + Send + Sync,
+{
+    pub fn new(test_runner: Arc<TR>, reporter: R, run_config: config::Config) -> Self {
+        Self {
+            test_runner,
+            reporter,
+            config: run_config,
+            timings: Timings::default(),
+            shard: None,
+        }
+    }
+
+    pub fn with_timings(self, timings: Timings) -> Self {
+        Self { timings, ..self }
+    }
+
+    pub fn with_shard(self, shard: Shard) -> Self {
+        Self {
+            shard: Some(shard),
+            ..self
+        }
+    }
+
+    /// Profiling times each test file on its own, so it turns batching off.
+    fn batch_size(&self) -> usize {
+        if self.config.profile {
+            1
+        } else {
+            self.config.batch_size.get()
+        }
+    }
+
+    fn order(&self, test_files: &[TestFile]) -> Vec<TestFile> {
+        let mut test_files = test_files.to_vec();
+        match self.config.order {
+            config::Order::Sorted => {}
+            config::Order::Random => shuffle(&mut test_files, self.config.seed.unwrap_or_default()),
+            config::Order::SlowestFirst => {
+                // Files without timings may be new and slow, so they go first
+                test_files.sort_by_key(|test_file| {
+                    std::cmp::Reverse(self.timings.get(test_file.name()).unwrap_or(u128::MAX))
+                });
+            }
+        }
+        test_files
+    }
+
+    pub async fn run(&self, test_files: &[TestFile]) -> TestSuiteReport {
+        let start = std::time::Instant::now();
+
+        let test_files = self.order(test_files);
+
+        let file_reports: Vec<TestFileReport> = stream::iter(&test_files)
+            .filter_map(|tf| async move {
+                match tf {
+                    TestFile::Valid(path) => Some(path.clone()),
+                    TestFile::NotFound(path) => {
+                        self.report(&ReportEvent::TestFileNotFound(path.clone()));
+                        None
+                    }
+                    TestFile::Invalid(path) => {
+                        self.report(&ReportEvent::TestFileInvalid(path.clone()));
+                        None
+                    }
+                }
+            })
+            .chunks(self.batch_size())
+            .map(|batch| {
+                let runner = self.test_runner.clone();
+                async move {
+                    if batch.len() == 1 {
+                        vec![runner.run(batch[0].clone()).await]
+                    } else {
+                        runner.run_batch(batch).await
+                    }
+                }
+            })
+            .buffer_unordered(self.config.num_threads.get())
+            .flat_map(stream::iter)
+            .inspect(|report| {
+                self.report(&ReportEvent::TestFileCompleted(report.clone()));
+            })
+            .collect()
+            .await;
+
+        let elapsed = start.elapsed().as_millis();
+        let mut suite_report = TestSuiteReport::new(file_reports, elapsed);
+        if self.config.order == config::Order::Random {
+            suite_report = suite_report.with_seed(self.config.seed.unwrap_or_default());
+        }
+        if let Some(shard) = self.shard {
+            suite_report = suite_report.with_shard(shard);
+        }
+
+        self.report(&ReportEvent::TestSuiteCompleted(suite_report.clone()));
+
+        suite_report
+    }
+
+    fn report(&self, event: &ReportEvent) {
+        if let Some(message) = self.reporter.on(event) {
+            print!("{}", message);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_suite_runner_tests {
+    use std::sync::Arc;
+
+    use futures::FutureExt;
+    use mockall::{predicate::eq, Sequence};
+
+    use super::*;
+    use crate::{
+        files::TestFile,
+        reports::{
+            MockReporter, ReportEvent, TestFileCompletedReport, TestFileReport, TestSuiteReport,
+        },
+        runners::config::Config,
+    };
+    use assert2::check;
+
+    #[tokio::test]
+    async fn it_runs_valid_tests() {
+        let mut test_runner = MockTestFileRunner::new();
+        test_runner
+            .expect_run()
+            .withf(|file| file == "my_test.nix")
+            .once()
+            .return_once(|_| {
+                async {
+                    TestFileReport::Completed(TestFileCompletedReport {
+                        file: "my_test.nix".to_string(),
+                        tests: vec![],
+                        elapsed: 0,
+                        mocks: vec![],
+                        cached: false,
+                        stats: None,
+                    })
+                }
+                .boxed()
+            });
+
+        let mut sequence = Sequence::new();
+
+        let mut reporter = MockReporter::new();
+        reporter
+            .expect_on()
+            .in_sequence(&mut sequence)
+            .once()
+            .with(eq(ReportEvent::TestFileCompleted(
+                TestFileReport::Completed(TestFileCompletedReport {
+                    file: "my_test.nix".to_string(),
+                    tests: vec![],
+                    elapsed: 0,
+                    mocks: vec![],
+                    cached: false,
+                    stats: None,
+                }),
+            )))
+            .returning(|_event| None);
+        reporter
+            .expect_on()
+            .once()
+            .in_sequence(&mut sequence)
+            .with(eq(ReportEvent::TestSuiteCompleted(TestSuiteReport::new(
+                vec![TestFileReport::Completed(TestFileCompletedReport {
+                    file: "my_test.nix".to_string(),
+                    tests: vec![],
+                    elapsed: 0,
+                    mocks: vec![],
+                    cached: false,
+                    stats: None,
+                })],
+                0,
+            ))))
+            .returning(|_event| None);
+
+        let suite_runner = TestSuiteRunner::new(Arc::new(test_runner), reporter, Config::default());
+
+        suite_runner
+            .run(&[TestFile::Valid("my_test.nix".to_string())])
+            .await;
+    }
+
+    #[tokio::test]
+    async fn it_runs_tests_in_batches() {
+        let completed = |file: &str| {
+            TestFileReport::Completed(TestFileCompletedReport {
+                file: file.to_string(),
+                tests: vec![],
+                elapsed: 0,
+                mocks: vec![],
+                cached: false,
+                stats: None,
+            })
+        };
+
+        let mut test_runner = MockTestFileRunner::new();
+        test_runner
+            .expect_run_batch()
+            .withf(|files| *files == ["a.nix", "b.nix"])
+            .once()
+            .return_once(move |_| {
+                async move { vec![completed("a.nix"), completed("b.nix")] }.boxed()
+            });
+        test_runner
+            .expect_run()
+            .withf(|file| file == "c.nix")
+            .once()
+            .return_once(move |_| async move { completed("c.nix") }.boxed());
+
+        let mut reporter = MockReporter::new();
+        reporter.expect_on().times(4).returning(|_event| None);
+
+        let suite_runner = TestSuiteRunner::new(
+            Arc::new(test_runner),
+            reporter,
+            Config {
+                batch_size: config::BatchSize::new(2),
+                ..Default::default()
+            },
+        );
+
+        suite_runner
+            .run(&[
+                TestFile::Valid("a.nix".to_string()),
+                TestFile::Valid("b.nix".to_string()),
+                TestFile::Valid("c.nix".to_string()),
+            ])
+            .await;
+    }
+
+    #[tokio::test]
+    async fn it_does_not_batch_profiled_test_files() {
+        let mut test_runner = MockTestFileRunner::new();
+        test_runner.expect_run_batch().never();
+        test_runner.expect_run().times(2).returning(|file| {
+            async move {
+                TestFileReport::Completed(TestFileCompletedReport {
+                    file,
+                    tests: vec![],
+                    elapsed: 0,
+                    mocks: vec![],
+                    cached: false,
+                    stats: None,
+                })
+            }
+            .boxed()
+        });
+
+        let mut reporter = MockReporter::new();
+        reporter.expect_on().times(3).returning(|_event| None);
+
+        let suite_runner = TestSuiteRunner::new(
+            Arc::new(test_runner),
+            reporter,
+            Config {
+                batch_size: config::BatchSize::new(2),
+                profile: true,
+                ..Default::default()
+            },
+        );
+
+        suite_runner
+            .run(&[
+                TestFile::Valid("a.nix".to_string()),
+                TestFile::Valid("b.nix".to_string()),
+            ])
+            .await;
+    }
+
+    fn ordered(config: Config, timings: Timings) -> Vec<String> {
+        let suite_runner = TestSuiteRunner::new(
+            Arc::new(MockTestFileRunner::new()),
+            MockReporter::new(),
+            config,
+        )
+        .with_timings(timings);
+
+        suite_runner
+            .order(
+                &["a.nix", "b.nix", "c.nix", "d.nix"].map(|file| TestFile::Valid(file.to_string())),
+            )
+            .iter()
+            .map(|test_file| test_file.name().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn it_shuffles_test_files_reproducibly() {
+        let config = |seed| Config {
+            order: config::Order::Random,
+            seed: Some(seed),
+            ..Default::default()
+        };
+
+        let shuffled = ordered(config(42), Timings::default());
+
+        check!(shuffled == ordered(config(42), Timings::default()));
+        check!(shuffled != ordered(config(7), Timings::default()));
+        check!(
+            ordered(Config::default(), Timings::default()) == ["a.nix", "b.nix", "c.nix", "d.nix"]
+        );
+    }
+
+    #[test]
+    fn it_runs_slowest_test_files_first() {
+        let completed = |file: &str, elapsed, cached| {
+            TestFileReport::Completed(TestFileCompletedReport {
+                file: file.to_string(),
+                tests: vec![],
+                elapsed,
+                mocks: vec![],
+                cached,
+                stats: None,
+            })
+        };
+
+        let mut timings = Timings::default();
+        timings.record(&[
+            completed("a.nix", 10, false),
+            completed("b.nix", 300, false),
+            completed("c.nix", 20, false),
+            // Cached reports don't reflect the evaluation time
+            completed("d.nix", 0, true),
+        ]);
+
+        let config = Config {
+            order: config::Order::SlowestFirst,
+            ..Default::default()
+        };
+
+        check!(ordered(config, timings) == ["d.nix", "b.nix", "c.nix", "a.nix"]);
+    }
+
+    #[test]
+    fn it_keeps_timings_for_each_project() {
+        let cache_dir = Path::new("/cache");
+        let path = Timings::path(cache_dir, Path::new("/projects/a"));
+
+        check!(path.starts_with("/cache/timings"));
+        check!(path == Timings::path(cache_dir, Path::new("/projects/a")));
+        check!(path != Timings::path(cache_dir, Path::new("/projects/b")));
+    }
+
+    #[tokio::test]
+    async fn it_skips_invalid_and_not_found_tests() {
+        let mut test_runner = MockTestFileRunner::new();
+        test_runner.expect_run().never();
+
+        let mut sequence = Sequence::new();
+        let mut reporter = MockReporter::new();
+        reporter
+            .expect_on()
+            .in_sequence(&mut sequence)
             .once()
-            .with(eq(ReportEvent::TestFileCompleted(
-                TestFileReport::Completed(TestFileCompletedReport {
-                    file: "my_test.nix".to_string(),
-                    tests: vec![],
-                    elapsed: 0,
-                }),
-            )))
+            .with(eq(ReportEvent::TestFileNotFound("missing.nix".to_string())))
+            .returning(|_event| None);
+        reporter
+            .expect_on()
+            .in_sequence(&mut sequence)
+            .once()
+            .with(eq(ReportEvent::TestFileInvalid("invalid.nix".to_string())))
             .returning(|_event| None);
         reporter
             .expect_on()
             .once()
             .in_sequence(&mut sequence)
             .with(eq(ReportEvent::TestSuiteCompleted(TestSuiteReport::new(
-                vec![TestFileReport::Completed(TestFileCompletedReport {
-                    file: "my_test.nix".to_string(),
-                    tests: vec![],
-                    elapsed: 0,
-                })],
+                vec![],
                 0,
             ))))
             .returning(|_event| None);
@@ -276,47 +2184,771 @@ mod test_suite_runner_tests {
         let suite_runner = TestSuiteRunner::new(Arc::new(test_runner), reporter, Config::default());
 
         suite_runner
-            .run(&[TestFile::Valid("my_test.nix".to_string())])
+            .run(&[
+                TestFile::NotFound("missing.nix".to_string()),
+                TestFile::Invalid("invalid.nix".to_string()),
+            ])
             .await;
     }
+}
+
+#[cfg(test)]
+mod resource_limits_tests {
+    use assert2::{check, let_assert};
+
+    use super::*;
+
+    fn limits() -> ResourceLimits {
+        ResourceLimits {
+            memory: Some(2048),
+            cpu_time: Some(30),
+            open_files: Some(64),
+        }
+    }
 
     #[tokio::test]
-    async fn it_skips_invalid_and_not_found_tests() {
-        let mut test_runner = MockTestFileRunner::new();
-        test_runner.expect_run().never();
+    async fn it_applies_limits_to_spawned_processes() {
+        let mut cmd = Command::new("sh");
+        cmd.args(["-c", "ulimit -n; ulimit -t; ulimit -v"]);
+        limits().apply(&mut cmd);
+
+        let output = cmd.output().await.unwrap();
+
+        check!(output.status.success());
+        check!(String::from_utf8_lossy(&output.stdout) == "64\n30\n2097152\n");
+    }
+
+    #[test]
+    fn it_classifies_exceeded_limits() {
+        let_assert!(
+            EvaluationError::ResourceExceeded(ResourceLimit::CpuTime(30)) =
+                limits().classify(String::new(), Some(libc::SIGXCPU), None)
+        );
+        let_assert!(
+            EvaluationError::ResourceExceeded(ResourceLimit::Memory(2048)) = limits().classify(
+                "error: out of memory".to_string(),
+                Some(libc::SIGABRT),
+                None
+            )
+        );
+        let_assert!(
+            EvaluationError::ResourceExceeded(ResourceLimit::OpenFiles(64)) = limits().classify(
+                "error: opening file '/tmp/a.nix': Too many open files".to_string(),
+                None,
+                None
+            )
+        );
+    }
+
+    #[test]
+    fn it_only_classifies_kills_as_cpu_time_once_it_is_used() {
+        let stats = |cpu_time: u64| ResourceStats {
+            peak_memory: 0,
+            user_time: cpu_time * 1000 - 500,
+            system_time: 500,
+            nix: None,
+        };
+
+        let_assert!(
+            EvaluationError::ResourceExceeded(ResourceLimit::CpuTime(30)) =
+                limits().classify(String::new(), Some(libc::SIGKILL), Some(&stats(31)))
+        );
+        let_assert!(
+            EvaluationError::Failed(_) =
+                limits().classify(String::new(), Some(libc::SIGKILL), Some(&stats(5)))
+        );
+        let_assert!(
+            EvaluationError::Failed(_) =
+                limits().classify(String::new(), Some(libc::SIGKILL), None)
+        );
+    }
+
+    #[test]
+    fn it_rejects_limits_with_the_repl_evaluator() {
+        let_assert!(
+            Err(error) = NixTestRunner::new(&config::Config {
+                evaluator: config::Evaluator::Repl,
+                max_memory: Some(2048),
+                ..Default::default()
+            })
+        );
+        check!(error.to_string().contains("repl evaluator"));
+    }
+
+    #[test]
+    fn it_rejects_profiling_with_the_repl_evaluator() {
+        let_assert!(
+            Err(error) = NixTestRunner::new(&config::Config {
+                evaluator: config::Evaluator::Repl,
+                profile: true,
+                ..Default::default()
+            })
+        );
+        check!(error.to_string().contains("repl evaluator"));
+    }
+
+    #[test]
+    fn it_only_classifies_configured_limits() {
+        let_assert!(
+            EvaluationError::Failed(error) = ResourceLimits::default().classify(
+                "error: out of memory".to_string(),
+                Some(libc::SIGXCPU),
+                None
+            )
+        );
+        check!(error == "error: out of memory");
+    }
+}
+
+#[cfg(test)]
+mod resource_stats_tests {
+    use std::fs;
+
+    use assert2::{check, let_assert};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn it_reads_usage_without_reaping_the_process() {
+        let mut child = Command::new("sh").args(["-c", "exit 3"]).spawn().unwrap();
+        let pid = child.id().unwrap();
+
+        let usage = tokio::task::spawn_blocking(move || exited_process_usage(pid))
+            .await
+            .unwrap();
+
+        let_assert!(Some(usage) = usage);
+        check!(resource_stats(&usage, None).peak_memory > 0);
+        check!(child.wait().await.unwrap().code() == Some(3));
+    }
+
+    #[test]
+    fn it_reads_nix_stats() {
+        let file = NixStatsFile::new();
+        fs::write(
+            &file.0,
+            r#"{"cpuTime":0.1,"nrThunks":120,"nrFunctionCalls":45,"nrPrimOpCalls":30,"gc":{"heapSize":4194304,"totalBytes":100}}"#,
+        )
+        .unwrap();
+
+        check!(
+            file.read()
+                == Some(NixStats {
+                    thunks: 120,
+                    function_calls: 45,
+                    primop_calls: 30,
+                    gc_heap_size: 4194304,
+                })
+        );
+
+        let path = file.0.clone();
+        drop(file);
+        check!(!path.exists());
+    }
+}
+
+#[cfg(test)]
+mod profile_tests {
+    use assert2::check;
+
+    use super::*;
+
+    #[test]
+    fn it_strips_case_labels_from_test_paths() {
+        let test = |path: &[&str], case: Option<&str>| TestReport {
+            success: true,
+            path: path.iter().map(|name| name.to_string()).collect(),
+            case: case.map(str::to_string),
+            location: "test.nix:1".to_string(),
+            checks: vec![],
+            elapsed: None,
+        };
+
+        check!(test_path(&test(&["group", "test"], None)) == vec!["group", "test"]);
+        check!(
+            test_path(&test(&["group", "test", "case 1"], Some("case 1"))) == vec!["group", "test"]
+        );
+    }
+
+    #[test]
+    fn it_limits_the_library_to_a_test_path() {
+        let runner = NixTestRunner::new(&config::Config {
+            lib_path: Some("lib/tests.nix".to_string()),
+            seed: Some(7),
+            ..Default::default()
+        })
+        .unwrap();
+
+        check!(runner
+            .nix_tests_expr(
+                Selection::Only(&["group".to_string(), "say \"hi\"".to_string()]),
+                false
+            )
+            .ends_with(
+                r#"{ seed = 7; propertyIterations = 100; only = [ "group" "say \"hi\"" ]; }"#
+            ));
+        check!(runner
+            .nix_tests_expr(Selection::All, false)
+            .ends_with("{ seed = 7; propertyIterations = 100; }"));
+        check!(runner
+            .nix_tests_expr(Selection::List, false)
+            .ends_with("{ seed = 7; propertyIterations = 100; listOnly = true; }"));
+        check!(runner
+            .nix_tests_expr(Selection::All, true)
+            .ends_with("{ seed = 7; propertyIterations = 100; traceTests = true; }"));
+    }
+}
+
+#[cfg(test)]
+mod build_tests {
+    use std::fs;
+
+    use assert2::check;
+    use tempfile::TempDir;
+
+    use super::*;
+    use crate::reports::BuildReport;
+
+    fn build_check(output_contains: Option<&str>) -> CheckReport {
+        CheckReport {
+            name: "builds".to_string(),
+            success: true,
+            failure: None,
+            location: "my_test.nix:10".to_string(),
+            property: None,
+            build: Some(BuildReport {
+                drv_path: "/nix/store/abc-foo.drv".to_string(),
+                output_contains: output_contains.map(str::to_string),
+                output: None,
+            }),
+            definitions: vec![],
+        }
+    }
+
+    #[test]
+    fn it_records_build_output() {
+        let mut check = build_check(None);
+
+        apply_build_result(&mut check, Ok("/nix/store/abc-foo".to_string()));
+
+        check!(check.success);
+        check!(check.failure == None);
+        check!(check.build.unwrap().output == Some("/nix/store/abc-foo".to_string()));
+    }
+
+    #[test]
+    fn it_fails_when_build_fails() {
+        let mut check = build_check(None);
+
+        apply_build_result(&mut check, Err("Build failed:\nboom".to_string()));
+
+        check!(!check.success);
+        check!(check.failure == Some("Build failed:\nboom".to_string()));
+    }
+
+    #[test]
+    fn it_checks_build_output_contains_path() {
+        let output = TempDir::new().unwrap();
+        fs::create_dir_all(output.path().join("bin")).unwrap();
+        fs::write(output.path().join("bin/foo"), b"").unwrap();
+        let output = output.path().to_string_lossy().to_string();
+
+        let mut found = build_check(Some("bin/foo"));
+        apply_build_result(&mut found, Ok(output.clone()));
+
+        let mut missing = build_check(Some("bin/bar"));
+        apply_build_result(&mut missing, Ok(output.clone()));
+
+        check!(found.success);
+        check!(!missing.success);
+        check!(
+            missing.failure
+                == Some(format!(
+                    "Expected: 'bin/bar' to exist in build output\nGot: {output}"
+                ))
+        );
+    }
+
+    #[test]
+    fn it_ignores_checks_without_builds() {
+        let mut check = CheckReport {
+            build: None,
+            definitions: vec![],
+            ..build_check(None)
+        };
+
+        apply_build_result(&mut check, Err("Build failed".to_string()));
+
+        check!(check.success);
+    }
+
+    #[test]
+    fn it_detects_derivations_missing_from_the_store() {
+        let dir = TempDir::new().unwrap();
+        let drv_path = dir.path().join("foo.drv");
+        let test = |check: CheckReport| TestReport {
+            success: true,
+            path: vec!["my_test".to_string()],
+            case: None,
+            location: "my_test.nix:10".to_string(),
+            checks: vec![check],
+            elapsed: None,
+        };
+        let written = CheckReport {
+            build: Some(BuildReport {
+                drv_path: drv_path.to_string_lossy().into_owned(),
+                output_contains: None,
+                output: None,
+            }),
+            ..build_check(None)
+        };
+
+        check!(has_unwritten_derivations(&[test(written.clone())]));
+        fs::write(&drv_path, "").unwrap();
+        check!(!has_unwritten_derivations(&[test(written)]));
+        check!(!has_unwritten_derivations(&[test(CheckReport {
+            build: None,
+            ..build_check(None)
+        })]));
+    }
+}
+
+#[cfg(test)]
+mod caching_runner_tests {
+    use std::fs;
+
+    use assert2::{check, let_assert};
+    use futures::FutureExt;
+    use tempfile::TempDir;
+
+    use super::*;
+
+    fn completed(file: &str) -> TestFileReport {
+        TestFileReport::Completed(TestFileCompletedReport {
+            file: file.to_string(),
+            tests: vec![],
+            elapsed: 10,
+            mocks: vec!["env.HOME".to_string()],
+            cached: false,
+            stats: None,
+        })
+    }
+
+    fn test_file(dir: &TempDir, name: &str, content: &str) -> String {
+        let path = dir.path().join(name);
+        fs::write(&path, content).unwrap();
+        path.to_string_lossy().to_string()
+    }
+
+    #[tokio::test]
+    async fn it_reuses_completed_reports() {
+        let dir = TempDir::new().unwrap();
+        let file = test_file(&dir, "my_test.nix", "{ }");
+
+        let mut inner = MockTestFileRunner::new();
+        inner
+            .expect_run()
+            .once()
+            .returning(|file| async move { completed(&file) }.boxed());
+
+        let runner = CachingRunner::new(inner, Some(ResultCache::new(dir.path().join("cache"), 1)));
+
+        let_assert!(TestFileReport::Completed(first) = runner.run(file.clone()).await);
+        check!(!first.cached);
+
+        let_assert!(TestFileReport::Completed(second) = runner.run(file.clone()).await);
+        check!(second.cached);
+        check!(second.file == file);
+        check!(second.elapsed == 10);
+        check!(second.mocks == vec!["env.HOME".to_string()]);
+    }
+
+    #[rstest::rstest]
+    #[case::random_seed(None, None, 1)]
+    #[case::same_seed(Some(7), Some(7), 0)]
+    #[case::other_seed(Some(7), Some(8), 1)]
+    #[tokio::test]
+    async fn it_reuses_property_tests_only_with_the_same_configured_seed(
+        #[case] first: Option<u32>,
+        #[case] second: Option<u32>,
+        #[case] reruns: usize,
+    ) {
+        let dir = TempDir::new().unwrap();
+        let file = test_file(&dir, "my_test.nix", "import ./properties.nix");
+        test_file(
+            &dir,
+            "properties.nix",
+            "helpers: { ok = helpers.forAll helpers.gen.ints (n: true); }",
+        );
+
+        let runner = |seed, runs| {
+            let mut inner = MockTestFileRunner::new();
+            inner
+                .expect_run()
+                .times(runs)
+                .returning(|file| async move { completed(&file) }.boxed());
+            CachingRunner::new(
+                inner,
+                Some(ResultCache::new(dir.path().join("cache"), 1).with_seed(seed)),
+            )
+        };
+
+        runner(first, 1).run(file.clone()).await;
+        let_assert!(TestFileReport::Completed(report) = runner(second, reruns).run(file).await);
+        check!(report.cached == (reruns == 0));
+    }
+
+    #[tokio::test]
+    async fn it_invalidates_on_changed_dependencies() {
+        let dir = TempDir::new().unwrap();
+        let file = test_file(&dir, "my_test.nix", "import ./lib.nix");
+        test_file(&dir, "lib.nix", "1");
+
+        let mut inner = MockTestFileRunner::new();
+        inner
+            .expect_run()
+            .times(2)
+            .returning(|file| async move { completed(&file) }.boxed());
+
+        let runner = CachingRunner::new(inner, Some(ResultCache::new(dir.path().join("cache"), 1)));
+
+        runner.run(file.clone()).await;
+        test_file(&dir, "lib.nix", "2");
+        let_assert!(TestFileReport::Completed(report) = runner.run(file).await);
+        check!(!report.cached);
+    }
+
+    #[tokio::test]
+    async fn it_does_not_cache_errored_reports() {
+        let dir = TempDir::new().unwrap();
+        let file = test_file(&dir, "my_test.nix", "{ }");
+
+        let mut inner = MockTestFileRunner::new();
+        inner.expect_run().times(2).returning(|file| {
+            async move {
+                TestFileReport::Errored(TestFileErroredReport {
+                    file,
+                    error: "error".to_string(),
+                    elapsed: 0,
+                    stats: None,
+                })
+            }
+            .boxed()
+        });
+
+        let runner = CachingRunner::new(inner, Some(ResultCache::new(dir.path().join("cache"), 1)));
+
+        runner.run(file.clone()).await;
+        runner.run(file).await;
+    }
+
+    #[tokio::test]
+    async fn it_invalidates_on_changed_environment_variables() {
+        let dir = TempDir::new().unwrap();
+        let file = test_file(
+            &dir,
+            "my_test.nix",
+            r#"{ home = builtins.getEnv "NIX_TESTS_CACHE_TEST_VAR"; }"#,
+        );
+
+        let mut inner = MockTestFileRunner::new();
+        inner
+            .expect_run()
+            .times(2)
+            .returning(|file| async move { completed(&file) }.boxed());
+
+        let runner = CachingRunner::new(inner, Some(ResultCache::new(dir.path().join("cache"), 1)));
+
+        env::set_var("NIX_TESTS_CACHE_TEST_VAR", "1");
+        runner.run(file.clone()).await;
+        env::set_var("NIX_TESTS_CACHE_TEST_VAR", "2");
+        let_assert!(TestFileReport::Completed(report) = runner.run(file).await);
+        check!(!report.cached);
+    }
+
+    #[rstest::rstest]
+    #[case::dynamic_path("import ./${\"lib\"}.nix")]
+    #[case::dynamic_environment_variable("builtins.getEnv name")]
+    #[tokio::test]
+    async fn it_does_not_cache_files_with_unknown_dependencies(#[case] content: &str) {
+        let dir = TempDir::new().unwrap();
+        let file = test_file(&dir, "my_test.nix", content);
+
+        let mut inner = MockTestFileRunner::new();
+        inner
+            .expect_run()
+            .times(2)
+            .returning(|file| async move { completed(&file) }.boxed());
+
+        let runner = CachingRunner::new(inner, Some(ResultCache::new(dir.path().join("cache"), 1)));
+
+        runner.run(file.clone()).await;
+        let_assert!(TestFileReport::Completed(report) = runner.run(file).await);
+        check!(!report.cached);
+    }
+
+    #[tokio::test]
+    async fn it_does_not_cache_build_checks() {
+        let dir = TempDir::new().unwrap();
+        let file = test_file(&dir, "my_test.nix", "{ }");
+
+        let mut inner = MockTestFileRunner::new();
+        inner.expect_run().times(2).returning(|file| {
+            async move {
+                let TestFileReport::Completed(report) = completed(&file) else {
+                    unreachable!()
+                };
+                TestFileReport::Completed(TestFileCompletedReport {
+                    tests: vec![TestReport {
+                        success: true,
+                        path: vec!["builds".to_string()],
+                        case: None,
+                        location: "my_test.nix:1".to_string(),
+                        checks: vec![CheckReport {
+                            name: "builds".to_string(),
+                            success: true,
+                            failure: None,
+                            location: "my_test.nix:2".to_string(),
+                            property: None,
+                            build: Some(crate::reports::BuildReport {
+                                drv_path: "/nix/store/abc-foo.drv".to_string(),
+                                output_contains: None,
+                                output: Some("/nix/store/abc-foo".to_string()),
+                            }),
+                            definitions: vec![],
+                        }],
+                        elapsed: None,
+                    }],
+                    ..report
+                })
+            }
+            .boxed()
+        });
+
+        let runner = CachingRunner::new(inner, Some(ResultCache::new(dir.path().join("cache"), 1)));
+
+        runner.run(file.clone()).await;
+        let_assert!(TestFileReport::Completed(report) = runner.run(file).await);
+        check!(!report.cached);
+    }
+
+    #[tokio::test]
+    async fn it_batches_only_uncached_files() {
+        let dir = TempDir::new().unwrap();
+        let a = test_file(&dir, "a.nix", "{ }");
+        let b = test_file(&dir, "b.nix", "{ }");
+        let c = test_file(&dir, "c.nix", "{ }");
+
+        let mut inner = MockTestFileRunner::new();
+        inner
+            .expect_run()
+            .once()
+            .returning(|file| async move { completed(&file) }.boxed());
+        let expected = vec![a.clone(), c.clone()];
+        inner
+            .expect_run_batch()
+            .withf(move |files| *files == expected)
+            .once()
+            .returning(|files| async move { files.iter().map(|f| completed(f)).collect() }.boxed());
+
+        let runner = CachingRunner::new(inner, Some(ResultCache::new(dir.path().join("cache"), 1)));
+
+        runner.run(b.clone()).await;
+        let reports = runner
+            .run_batch(vec![a.clone(), b.clone(), c.clone()])
+            .await;
+
+        let files: Vec<(String, bool)> = reports
+            .into_iter()
+            .map(|report| match report {
+                TestFileReport::Completed(report) => (report.file, report.cached),
+                _ => unreachable!(),
+            })
+            .collect();
+        check!(files == vec![(a, false), (b, true), (c, false)]);
+    }
+
+    #[tokio::test]
+    async fn it_passes_through_without_a_cache() {
+        let mut inner = MockTestFileRunner::new();
+        inner
+            .expect_run()
+            .times(2)
+            .returning(|file| async move { completed(&file) }.boxed());
+
+        let runner = CachingRunner::new(inner, None);
+
+        runner.run("my_test.nix".to_string()).await;
+        runner.run("my_test.nix".to_string()).await;
+    }
+}
+
+#[cfg(test)]
+mod lib_path_tests {
+    use std::fs;
+
+    use assert2::{check, let_assert};
+    use tempfile::TempDir;
+
+    use super::*;
+
+    #[test]
+    fn it_materialises_the_bundled_library() {
+        let dir = TempDir::new().unwrap();
+
+        let path = materialise_lib(dir.path()).unwrap();
+
+        check!(path.starts_with(dir.path()));
+        check!(fs::read_to_string(&path).unwrap() == NIX_TESTS_LIB);
+        check!(materialise_lib(dir.path()).unwrap() == path);
+    }
+
+    #[test]
+    fn it_prefers_the_configured_library() {
+        let dir = TempDir::new().unwrap();
+        let lib = dir.path().join("tests.nix");
+        fs::write(&lib, "{ ... }: { }").unwrap();
+
+        let path = resolve_lib_path(Some(lib.to_string_lossy().to_string()), dir.path()).unwrap();
+
+        check!(path == lib.to_string_lossy());
+    }
+
+    #[test]
+    fn it_fails_when_the_configured_library_is_missing() {
+        let dir = TempDir::new().unwrap();
+
+        let result = resolve_lib_path(Some("/tmp/not_existing.nix".to_string()), dir.path());
+
+        let_assert!(Err(err) = result);
+        check!(err.to_string() == "Nix library not found at '/tmp/not_existing.nix'");
+    }
+}
+
+#[cfg(test)]
+mod overrides_tests {
+    use std::collections::BTreeMap;
+
+    use assert2::check;
+
+    use super::*;
+    use crate::runners::config::Overrides;
+
+    #[test]
+    fn it_lists_active_mocks() {
+        let overrides = Overrides {
+            env: BTreeMap::from([("HOME".to_string(), "/tmp".to_string())]),
+            builtins: BTreeMap::from([(
+                "currentSystem".to_string(),
+                serde_json::json!("aarch64-linux"),
+            )]),
+            imports: BTreeMap::from([(
+                "/src/secrets.nix".to_string(),
+                "/src/fake-secrets.nix".to_string(),
+            )]),
+        };
+
+        check!(
+            overrides.names()
+                == vec![
+                    "env.HOME",
+                    "builtins.currentSystem",
+                    "imports./src/secrets.nix"
+                ]
+        );
+    }
+
+    #[test]
+    fn it_resolves_import_overrides_to_absolute_paths() {
+        let overrides = Overrides {
+            imports: BTreeMap::from([(
+                "secrets.nix".to_string(),
+                "/src/fake-secrets.nix".to_string(),
+            )]),
+            ..Default::default()
+        };
+
+        let json: serde_json::Value = serde_json::from_str(&overrides_json(&overrides)).unwrap();
+        let secrets = std::path::absolute("secrets.nix").unwrap();
+
+        check!(json["imports"][secrets.to_string_lossy().as_ref()] == "/src/fake-secrets.nix");
+    }
+
+    #[test]
+    fn it_adds_traced_mocks_to_the_runner_overrides() {
+        let runner = NixTestRunner::new(&config::Config {
+            overrides: Overrides {
+                env: BTreeMap::from([("HOME".to_string(), "/tmp".to_string())]),
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+        .unwrap();
+
+        let traces = [
+            "nix-tests mock: builtins.currentSystem",
+            "something else",
+            "nix-tests mock: env.HOME",
+            "nix-tests mock: builtins.currentSystem",
+        ]
+        .map(str::to_string);
 
-        let mut sequence = Sequence::new();
-        let mut reporter = MockReporter::new();
-        reporter
-            .expect_on()
-            .in_sequence(&mut sequence)
-            .once()
-            .with(eq(ReportEvent::TestFileNotFound("missing.nix".to_string())))
-            .returning(|_event| None);
-        reporter
-            .expect_on()
-            .in_sequence(&mut sequence)
-            .once()
-            .with(eq(ReportEvent::TestFileInvalid("invalid.nix".to_string())))
-            .returning(|_event| None);
-        reporter
-            .expect_on()
-            .once()
-            .in_sequence(&mut sequence)
-            .with(eq(ReportEvent::TestSuiteCompleted(TestSuiteReport::new(
-                vec![],
-                0,
-            ))))
-            .returning(|_event| None);
+        check!(runner.active_mocks(&traces) == vec!["env.HOME", "builtins.currentSystem"]);
+    }
 
-        let suite_runner = TestSuiteRunner::new(Arc::new(test_runner), reporter, Config::default());
+    #[test]
+    fn it_groups_batch_traces_by_file() {
+        let start = Instant::now();
+        let trace = |message: &str, at: u64| Trace {
+            message: message.to_string(),
+            at: start + Duration::from_millis(at),
+        };
+        let traces = vec![
+            trace("before any file", 0),
+            trace("nix-tests file: a_test.nix", 10),
+            trace("nix-tests mock: env.HOME", 15),
+            trace(r#"nix-tests result: {"tests":[]}"#, 35),
+            trace("nix-tests file: b_test.nix", 40),
+            trace("nix-tests file: c_test.nix", 45),
+            trace("nix-tests mock: builtins.currentSystem", 50),
+        ];
 
-        suite_runner
-            .run(&[
-                TestFile::NotFound("missing.nix".to_string()),
-                TestFile::Invalid("invalid.nix".to_string()),
-            ])
-            .await;
+        check!(
+            batch_traces(traces, start + Duration::from_millis(100))
+                == BTreeMap::from([
+                    (
+                        "a_test.nix".to_string(),
+                        BatchFileTraces {
+                            messages: vec!["nix-tests mock: env.HOME".to_string()],
+                            elapsed: 30,
+                            entry: Some(BatchEntry {
+                                tests: Some(vec![])
+                            }),
+                        }
+                    ),
+                    (
+                        "b_test.nix".to_string(),
+                        BatchFileTraces {
+                            messages: vec![],
+                            elapsed: 5,
+                            entry: None,
+                        }
+                    ),
+                    (
+                        "c_test.nix".to_string(),
+                        BatchFileTraces {
+                            messages: vec!["nix-tests mock: builtins.currentSystem".to_string()],
+                            elapsed: 55,
+                            entry: None,
+                        }
+                    ),
+                ])
+        );
+    }
+
+    #[test]
+    fn it_escapes_nix_strings() {
+        check!(nix_string(r#"/a "b" \c ${d}"#) == r#"/a \"b\" \\c \${d}"#);
     }
 }
 
@@ -334,157 +2966,571 @@ mod runner_tests {
         let mut file = NamedTempFile::new().unwrap();
         file.write_all(content.as_bytes()).unwrap();
 
-        let path = file.path().to_str().unwrap().to_string();
+        let path = file.path().to_str().unwrap().to_string();
+
+        (file, path)
+    }
+
+    #[tokio::test]
+    async fn it_runs_a_simple_test_file() {
+        let (_file, path) = create_temp_nix_file(
+            r#"{
+  pkgs ? import <nixpkgs> { },
+  nix-tests,
+}:
+let
+  lib = pkgs.lib;
+
+  # Custom check
+  isEven = x: if lib.mod x 2 == 0 then true else "${builtins.toString x} is not even";
+in
+nix-tests.runTests {
+  "success" = helpers: rec {
+    ctx = {
+      num = 42;
+    };
+    "number equals 42" = helpers.isEq ctx.num 42;
+    "number is even" = helpers.check isEven ctx.num;
+  };
+
+  "failure" = helpers: {
+    "failed check" = helpers.isTrue false;
+  };
+}
+"#,
+        );
+
+        let report = NixTestRunner::new(&config::Config::default())
+            .unwrap()
+            .run(path.clone())
+            .await;
+
+        let_assert!(TestFileReport::Completed(file_report) = report);
+        check!(file_report.file == path);
+        check!(file_report.elapsed > 0);
+        check!(
+            file_report.tests
+                == vec![
+                    TestReport {
+                        success: true,
+                        path: vec!["success".to_string()],
+                        case: None,
+                        location: format!("{}:12", path),
+                        checks: vec![
+                            CheckReport {
+                                name: "number equals 42".to_string(),
+                                success: true,
+                                failure: None,
+                                location: format!("{}:16", path),
+                                property: None,
+                                build: None,
+                                definitions: vec![],
+                            },
+                            CheckReport {
+                                name: "number is even".to_string(),
+                                success: true,
+                                failure: None,
+                                location: format!("{}:17", path),
+                                property: None,
+                                build: None,
+                                definitions: vec![],
+                            },
+                        ],
+                        elapsed: None,
+                    },
+                    TestReport {
+                        success: false,
+                        path: vec!["failure".to_string()],
+                        case: None,
+                        location: format!("{}:20", path),
+                        checks: vec![CheckReport {
+                            name: "failed check".to_string(),
+                            success: false,
+                            failure: Some("Expected: true\nGot: false".to_string()),
+                            location: format!("{}:21", path),
+                            property: None,
+                            build: None,
+                            definitions: vec![],
+                        }],
+                        elapsed: None,
+                    },
+                ]
+        );
+    }
+
+    #[tokio::test]
+    async fn it_runs_a_groups_test_file() {
+        let (_file, path) = create_temp_nix_file(
+            r#"{
+  pkgs ? import <nixpkgs> { },
+  nix-tests,
+}:
+nix-tests.runTests {
+  "group 1" = {
+    "test 1" = helpers: {
+      "check 1" = helpers.isTrue true;
+    };
+    "test 2" = helpers: {
+      "check 2" = helpers.isTrue true;
+    };
+  };
+  "group 2" = {
+    "test 3" = helpers: {
+      "check 3" = helpers.isTrue true;
+    };
+  };
+}
+"#,
+        );
+
+        let report = NixTestRunner::new(&config::Config::default())
+            .unwrap()
+            .run(path.clone())
+            .await;
+
+        let_assert!(TestFileReport::Completed(file_report) = report);
+        check!(file_report.file == path);
+        check!(file_report.elapsed > 0);
+        check!(
+            file_report.tests
+                == vec![
+                    TestReport {
+                        success: true,
+                        path: vec!["group 1".to_string(), "test 1".to_string()],
+                        case: None,
+                        location: format!("{}:7", path),
+                        checks: vec![CheckReport {
+                            name: "check 1".to_string(),
+                            success: true,
+                            failure: None,
+                            location: format!("{}:8", path),
+                            property: None,
+                            build: None,
+                            definitions: vec![],
+                        },],
+                        elapsed: None,
+                    },
+                    TestReport {
+                        success: true,
+                        path: vec!["group 1".to_string(), "test 2".to_string()],
+                        case: None,
+                        location: format!("{}:10", path),
+                        checks: vec![CheckReport {
+                            name: "check 2".to_string(),
+                            success: true,
+                            failure: None,
+                            location: format!("{}:11", path),
+                            property: None,
+                            build: None,
+                            definitions: vec![],
+                        },],
+                        elapsed: None,
+                    },
+                    TestReport {
+                        success: true,
+                        path: vec!["group 2".to_string(), "test 3".to_string()],
+                        case: None,
+                        location: format!("{}:15", path),
+                        checks: vec![CheckReport {
+                            name: "check 3".to_string(),
+                            success: true,
+                            failure: None,
+                            location: format!("{}:16", path),
+                            property: None,
+                            build: None,
+                            definitions: vec![],
+                        },],
+                        elapsed: None,
+                    }
+                ]
+        );
+    }
+
+    #[tokio::test]
+    async fn it_labels_repeated_cases_by_index() {
+        let (_file, path) = create_temp_nix_file(
+            r#"{ nix-tests }:
+nix-tests.runTests {
+  "numbers" = helpers:
+    helpers.forEach [ 1 2 1 ] (n: {
+      "is positive" = helpers.isTrue (n > 0);
+    });
+}
+"#,
+        );
+
+        let report = NixTestRunner::new(&config::Config::default())
+            .unwrap()
+            .run(path.clone())
+            .await;
+
+        let_assert!(TestFileReport::Completed(file_report) = report);
+        let cases: Vec<Option<&str>> = file_report
+            .tests
+            .iter()
+            .map(|test| test.case.as_deref())
+            .collect();
+        check!(cases == [Some("1 #0"), Some("2"), Some("1 #2")]);
+    }
+
+    #[tokio::test]
+    async fn it_runs_a_parameterised_test_file() {
+        let (_file, path) = create_temp_nix_file(
+            r#"{
+  pkgs ? import <nixpkgs> { },
+  nix-tests,
+}:
+nix-tests.runTests {
+  "systems" = helpers:
+    helpers.forEach [ "x86_64-linux" "aarch64-linux" ] (system: {
+      "is linux" = helpers.isTrue (builtins.match ".*-linux" system != null);
+    });
+  "numbers" = helpers:
+    helpers.forEach {
+      small = 1;
+      big = 100;
+    } (n: {
+      "is small" = helpers.check (x: if x < 10 then true else "${toString x} is not small") n;
+    });
+}
+"#,
+        );
+
+        let report = NixTestRunner::new(&config::Config::default())
+            .unwrap()
+            .run(path.clone())
+            .await;
+
+        let_assert!(TestFileReport::Completed(file_report) = report);
+        check!(
+            file_report.tests
+                == vec![
+                    TestReport {
+                        success: true,
+                        path: vec!["systems".to_string(), "x86_64-linux".to_string()],
+                        case: Some("x86_64-linux".to_string()),
+                        location: format!("{}:6", path),
+                        checks: vec![CheckReport {
+                            name: "is linux".to_string(),
+                            success: true,
+                            failure: None,
+                            location: format!("{}:8", path),
+                            property: None,
+                            build: None,
+                            definitions: vec![],
+                        }],
+                        elapsed: None,
+                    },
+                    TestReport {
+                        success: true,
+                        path: vec!["systems".to_string(), "aarch64-linux".to_string()],
+                        case: Some("aarch64-linux".to_string()),
+                        location: format!("{}:6", path),
+                        checks: vec![CheckReport {
+                            name: "is linux".to_string(),
+                            success: true,
+                            failure: None,
+                            location: format!("{}:8", path),
+                            property: None,
+                            build: None,
+                            definitions: vec![],
+                        }],
+                        elapsed: None,
+                    },
+                    TestReport {
+                        success: true,
+                        path: vec!["numbers".to_string(), "small".to_string()],
+                        case: Some("small".to_string()),
+                        location: format!("{}:12", path),
+                        checks: vec![CheckReport {
+                            name: "is small".to_string(),
+                            success: true,
+                            failure: None,
+                            location: format!("{}:15", path),
+                            property: None,
+                            build: None,
+                            definitions: vec![],
+                        }],
+                        elapsed: None,
+                    },
+                    TestReport {
+                        success: false,
+                        path: vec!["numbers".to_string(), "big".to_string()],
+                        case: Some("big".to_string()),
+                        location: format!("{}:13", path),
+                        checks: vec![CheckReport {
+                            name: "is small".to_string(),
+                            success: false,
+                            failure: Some("100 is not small".to_string()),
+                            location: format!("{}:15", path),
+                            property: None,
+                            build: None,
+                            definitions: vec![],
+                        }],
+                        elapsed: None,
+                    },
+                ]
+        );
+    }
+
+    #[tokio::test]
+    async fn it_runs_a_property_test_file() {
+        let (_file, path) = create_temp_nix_file(
+            r#"{
+  pkgs ? import <nixpkgs> { },
+  nix-tests,
+}:
+nix-tests.runTests {
+  "properties" = helpers: {
+    "successor is greater" = helpers.forAll helpers.gen.ints (n: n + 1 > n);
+    "numbers are small" = helpers.forAll helpers.gen.ints (
+      n: if n < 10 then true else "${toString n} is not small"
+    );
+  };
+}
+"#,
+        );
+
+        let report = NixTestRunner::new(&config::Config {
+            seed: Some(42),
+            ..Default::default()
+        })
+        .unwrap()
+        .run(path.clone())
+        .await;
+
+        let_assert!(TestFileReport::Completed(file_report) = report);
+        let_assert!([test] = file_report.tests.as_slice());
+        let_assert!([succeeded, failed] = test.checks.as_slice());
+        check!(succeeded.success);
+        check!(succeeded.property == None);
+        check!(!failed.success);
+        check!(failed.failure == Some("10 is not small".to_string()));
+        let_assert!(Some(property) = &failed.property);
+        check!(property.seed == 42);
+        check!(property.input == serde_json::json!(10));
+    }
+
+    #[tokio::test]
+    async fn it_skips_shrink_candidates_that_throw() {
+        let (_file, path) = create_temp_nix_file(
+            r#"{
+  pkgs ? import <nixpkgs> { },
+  nix-tests,
+}:
+nix-tests.runTests {
+  "properties" = helpers: {
+    "numbers are small" = helpers.forAll {
+      generate = state: _: {
+        value = 10;
+        inherit state;
+      };
+      shrink = n: if n == 10 then [ 0 5 ] else [ ];
+    } (n: if n == 0 then throw "zero" else n < 5);
+  };
+}
+"#,
+        );
+
+        let report = NixTestRunner::new(&config::Config {
+            seed: Some(42),
+            ..Default::default()
+        })
+        .unwrap()
+        .run(path.clone())
+        .await;
 
-        (file, path)
+        let_assert!(TestFileReport::Completed(file_report) = report);
+        let_assert!([test] = file_report.tests.as_slice());
+        let_assert!([failed] = test.checks.as_slice());
+        check!(!failed.success);
+        let_assert!(Some(property) = &failed.property);
+        check!(property.input == serde_json::json!(5));
+        check!(property.shrinks == 1);
     }
 
     #[tokio::test]
-    async fn it_runs_a_simple_test_file() {
+    async fn it_runs_a_build_test_file() {
         let (_file, path) = create_temp_nix_file(
             r#"{
   pkgs ? import <nixpkgs> { },
   nix-tests,
 }:
 let
-  lib = pkgs.lib;
-
-  # Custom check
-  isEven = x: if lib.mod x 2 == 0 then true else "${builtins.toString x} is not even";
+  drv = pkgs.runCommand "nix-tests-build" { } "mkdir -p $out/bin && touch $out/bin/foo";
 in
 nix-tests.runTests {
-  "success" = helpers: rec {
-    ctx = {
-      num = 42;
-    };
-    "number equals 42" = helpers.isEq ctx.num 42;
-    "number is even" = helpers.check isEven ctx.num;
-  };
-
-  "failure" = helpers: {
-    "failed check" = helpers.isTrue false;
+  "build" = helpers: {
+    "builds" = helpers.builds drv;
+    "contains foo" = helpers.buildOutputContains drv "bin/foo";
+    "contains bar" = helpers.buildOutputContains drv "bin/bar";
   };
 }
 "#,
         );
 
-        let report = NixTestRunner::new(0).run(path.clone()).await;
+        let report = NixTestRunner::new(&config::Config::default())
+            .unwrap()
+            .run(path.clone())
+            .await;
 
         let_assert!(TestFileReport::Completed(file_report) = report);
-        check!(file_report.file == path);
-        check!(file_report.elapsed > 0);
-        check!(
-            file_report.tests
-                == vec![
-                    TestReport {
-                        success: true,
-                        path: vec!["success".to_string()],
-                        location: format!("{}:12", path),
-                        checks: vec![
-                            CheckReport {
-                                name: "number equals 42".to_string(),
-                                success: true,
-                                failure: None,
-                                location: format!("{}:16", path),
-                            },
-                            CheckReport {
-                                name: "number is even".to_string(),
-                                success: true,
-                                failure: None,
-                                location: format!("{}:17", path),
-                            },
-                        ]
-                    },
-                    TestReport {
-                        success: false,
-                        path: vec!["failure".to_string()],
-                        location: format!("{}:20", path),
-                        checks: vec![CheckReport {
-                            name: "failed check".to_string(),
-                            success: false,
-                            failure: Some("Expected: true\nGot: false".to_string()),
-                            location: format!("{}:21", path),
-                        }]
-                    },
-                ]
-        );
+        let_assert!([test] = file_report.tests.as_slice());
+        check!(!test.success);
+        let_assert!([builds, contains_foo, contains_bar] = test.checks.as_slice());
+        check!(builds.success);
+        check!(contains_foo.success);
+        check!(!contains_bar.success);
+        let_assert!(Some(build) = &builds.build);
+        let_assert!(Some(output) = &build.output);
+        check!(Path::new(output).join("bin/foo").exists());
     }
 
     #[tokio::test]
-    async fn it_runs_a_groups_test_file() {
+    async fn it_runs_a_module_test_file() {
         let (_file, path) = create_temp_nix_file(
             r#"{
   pkgs ? import <nixpkgs> { },
   nix-tests,
 }:
-nix-tests.runTests {
-  "group 1" = {
-    "test 1" = helpers: {
-      "check 1" = helpers.isTrue true;
-    };
-    "test 2" = helpers: {
-      "check 2" = helpers.isTrue true;
-    };
+let
+  lib = pkgs.lib;
+  module = {
+    options.port = lib.mkOption { type = lib.types.port; };
+    options.assertions = lib.mkOption { type = lib.types.listOf lib.types.unspecified; default = [ ]; };
+    options.warnings = lib.mkOption { type = lib.types.listOf lib.types.str; default = [ ]; };
   };
-  "group 2" = {
-    "test 3" = helpers: {
-      "check 3" = helpers.isTrue true;
+in
+nix-tests.runTests {
+  "module" = helpers: rec {
+    evaluated = helpers.evalModule {
+      inherit lib;
+      modules = [
+        module
+        ({ config, ... }: {
+          port = 8080;
+          assertions = [ { assertion = config.port < 1024; message = "port must be privileged"; } ];
+          warnings = [ "port is not privileged" ];
+        })
+      ];
     };
+
+    "has port" = helpers.hasOption "port" evaluated;
+    "has no host" = helpers.hasOption "host" evaluated;
+    "port is 80" = helpers.optionEquals "port" 80 evaluated;
+    "assertion fails" = helpers.assertionsFail "privileged" evaluated;
+    "warns" = helpers.warningsContain "not privileged" evaluated;
   };
 }
 "#,
         );
 
-        let report = NixTestRunner::new(0).run(path.clone()).await;
+        let report = NixTestRunner::new(&config::Config::default())
+            .unwrap()
+            .run(path.clone())
+            .await;
 
         let_assert!(TestFileReport::Completed(file_report) = report);
-        check!(file_report.file == path);
-        check!(file_report.elapsed > 0);
+        let_assert!([test] = file_report.tests.as_slice());
+        let_assert!(
+            [has_port, has_no_host, port_is_80, assertion_fails, warns] = test.checks.as_slice()
+        );
+        check!(has_port.success);
+        check!(!has_no_host.success);
         check!(
-            file_report.tests
+            has_no_host.failure
+                == Some("Expected: option 'host' to exist\nGot: missing".to_string())
+        );
+        check!(!port_is_80.success);
+        check!(port_is_80.failure == Some("Expected: 80\nGot: 8080".to_string()));
+        check!(port_is_80.definitions.len() == 1);
+        check!(assertion_fails.success);
+        check!(warns.success);
+    }
+
+    #[tokio::test]
+    async fn it_runs_a_test_file_with_mocks() {
+        let (_secrets, secrets_path) = create_temp_nix_file(r#"{ password = "real"; }"#);
+        let (_fake, fake_path) = create_temp_nix_file(r#"{ password = "fake"; }"#);
+        let (_file, path) = create_temp_nix_file(&format!(
+            r#"{{
+  pkgs ? import <nixpkgs> {{ }},
+  nix-tests,
+}}:
+nix-tests.runTests {{
+  "mocks" = helpers: {{
+    "env is mocked" = helpers.isEq (builtins.getEnv "NIX_TESTS_MOCKED") "yes";
+    "system is mocked" = helpers.isEq builtins.currentSystem "riscv64-linux";
+    "import is mocked" = helpers.isEq (import {secrets_path}).password "fake";
+  }};
+}}
+"#
+        ));
+
+        let config = config::Config {
+            overrides: config::Overrides {
+                env: BTreeMap::from([("NIX_TESTS_MOCKED".to_string(), "yes".to_string())]),
+                builtins: BTreeMap::from([(
+                    "currentSystem".to_string(),
+                    serde_json::json!("riscv64-linux"),
+                )]),
+                imports: BTreeMap::from([(secrets_path.clone(), fake_path)]),
+            },
+            ..Default::default()
+        };
+        let report = NixTestRunner::new(&config).unwrap().run(path.clone()).await;
+
+        let_assert!(TestFileReport::Completed(file_report) = report);
+        let_assert!([test] = file_report.tests.as_slice());
+        check!(test.success);
+        check!(
+            file_report.mocks
                 == vec![
-                    TestReport {
-                        success: true,
-                        path: vec!["group 1".to_string(), "test 1".to_string()],
-                        location: format!("{}:7", path),
-                        checks: vec![CheckReport {
-                            name: "check 1".to_string(),
-                            success: true,
-                            failure: None,
-                            location: format!("{}:8", path),
-                        },]
-                    },
-                    TestReport {
-                        success: true,
-                        path: vec!["group 1".to_string(), "test 2".to_string()],
-                        location: format!("{}:10", path),
-                        checks: vec![CheckReport {
-                            name: "check 2".to_string(),
-                            success: true,
-                            failure: None,
-                            location: format!("{}:11", path),
-                        },]
-                    },
-                    TestReport {
-                        success: true,
-                        path: vec!["group 2".to_string(), "test 3".to_string()],
-                        location: format!("{}:15", path),
-                        checks: vec![CheckReport {
-                            name: "check 3".to_string(),
-                            success: true,
-                            failure: None,
-                            location: format!("{}:16", path),
-                        },]
-                    }
+                    "env.NIX_TESTS_MOCKED".to_string(),
+                    "builtins.currentSystem".to_string(),
+                    format!("imports.{secrets_path}"),
                 ]
         );
     }
 
+    #[tokio::test]
+    async fn it_reports_mocks_set_up_by_test_files() {
+        let (_module, module_path) = create_temp_nix_file(r#"{ home = builtins.getEnv "HOME"; }"#);
+        let (_file, path) = create_temp_nix_file(&format!(
+            r#"{{
+  pkgs ? import <nixpkgs> {{ }},
+  nix-tests,
+}}:
+let
+  module = nix-tests.mockImport {{ env.HOME = "/home/test"; }} {module_path};
+in
+nix-tests.runTests {{
+  "mocks" = helpers: {{
+    "home is mocked" = helpers.isEq module.home "/home/test";
+  }};
+}}
+"#
+        ));
+
+        let report = NixTestRunner::new(&config::Config::default())
+            .unwrap()
+            .run(path.clone())
+            .await;
+
+        let_assert!(TestFileReport::Completed(file_report) = report);
+        let_assert!([test] = file_report.tests.as_slice());
+        check!(test.success);
+        check!(file_report.mocks == vec!["env.HOME".to_string()]);
+    }
+
     #[tokio::test]
     async fn it_handles_nix_evaluation_errors() {
         let (_file, path) = create_temp_nix_file("invalid_nix_syntax_here");
 
-        let report = NixTestRunner::new(0).run(path.clone()).await;
+        let report = NixTestRunner::new(&config::Config::default())
+            .unwrap()
+            .run(path.clone())
+            .await;
 
         let_assert!(TestFileReport::Errored(err_report) = report);
         check!(err_report.error.contains("error:"));
@@ -506,7 +3552,10 @@ nix-tests.runTests {
 "#,
         );
 
-        let report = NixTestRunner::new(0).run(path.clone()).await;
+        let report = NixTestRunner::new(&config::Config::default())
+            .unwrap()
+            .run(path.clone())
+            .await;
 
         let_assert!(TestFileReport::Errored(err_report) = report);
         check!(err_report
@@ -517,7 +3566,10 @@ nix-tests.runTests {
     #[tokio::test]
     async fn it_handles_command_execution_failure() {
         let invalid_path = "test\0file.nix".to_string();
-        let report = NixTestRunner::new(0).run(invalid_path.clone()).await;
+        let report = NixTestRunner::new(&config::Config::default())
+            .unwrap()
+            .run(invalid_path.clone())
+            .await;
 
         let_assert!(TestFileReport::Errored(err_report) = report);
         check!(err_report
@@ -545,7 +3597,13 @@ in
         );
 
         let timeout_ms = 50;
-        let report = NixTestRunner::new(timeout_ms).run(path.clone()).await;
+        let report = NixTestRunner::new(&config::Config {
+            timeout: timeout_ms,
+            ..Default::default()
+        })
+        .unwrap()
+        .run(path.clone())
+        .await;
 
         let_assert!(TestFileReport::TimedOut(file_report) = report);
         check!(file_report.file == path);
@@ -553,4 +3611,281 @@ in
         check!(file_report.elapsed >= timeout_ms as u128);
         check!(file_report.elapsed < (timeout_ms + 100) as u128);
     }
+
+    #[tokio::test]
+    async fn it_runs_test_files_in_a_repl() {
+        let (_file, path) = create_temp_nix_file(
+            r#"{
+  pkgs ? import <nixpkgs> { },
+  nix-tests,
+}:
+nix-tests.runTests {
+  "repl" = helpers: {
+    "uses shared pkgs" = helpers.isEq (pkgs.lib.toUpper "ok") "OK";
+  };
+}
+"#,
+        );
+
+        let runner = NixTestRunner::new(&config::Config {
+            evaluator: config::Evaluator::Repl,
+            ..Default::default()
+        })
+        .unwrap();
+
+        for _ in 0..2 {
+            let_assert!(TestFileReport::Completed(file_report) = runner.run(path.clone()).await);
+            check!(file_report.tests.len() == 1);
+            check!(file_report.tests[0].success);
+        }
+    }
+
+    #[tokio::test]
+    async fn it_reports_repl_evaluation_errors() {
+        let (_file, path) = create_temp_nix_file("{ nix-tests }: { tests = undefinedVariable; }\n");
+
+        let report = NixTestRunner::new(&config::Config {
+            evaluator: config::Evaluator::Repl,
+            ..Default::default()
+        })
+        .unwrap()
+        .run(path.clone())
+        .await;
+
+        let_assert!(TestFileReport::Errored(err_report) = report);
+        check!(err_report.error.contains("undefined variable"));
+    }
+
+    #[tokio::test]
+    async fn it_runs_test_files_in_a_batch() {
+        let (_passing, passing) = create_temp_nix_file(
+            r#"{ nix-tests }:
+nix-tests.runTests {
+  "batched" = helpers: {
+    "passes" = helpers.isTrue true;
+  };
+}
+"#,
+        );
+        let (_failing, failing) =
+            create_temp_nix_file("{ nix-tests }: { tests = throw \"broken test file\"; }\n");
+
+        let reports = NixTestRunner::new(&config::Config::default())
+            .unwrap()
+            .run_batch(vec![passing.clone(), failing.clone()])
+            .await;
+
+        let_assert!(
+            [
+                TestFileReport::Completed(passed),
+                TestFileReport::Errored(errored)
+            ] = &reports[..]
+        );
+        check!(passed.file == passing);
+        check!(passed.tests.len() == 1);
+        check!(errored.file == failing);
+        check!(errored.error.contains("broken test file"));
+    }
+
+    #[tokio::test]
+    async fn it_keeps_files_evaluated_before_a_batch_fails() {
+        let (_passing, passing) = create_temp_nix_file(
+            r#"{ nix-tests }:
+nix-tests.runTests {
+  "batched" = helpers: {
+    "passes" = helpers.isTrue true;
+  };
+}
+"#,
+        );
+        // Unlike a throw, an abort fails the whole batch
+        let (_aborting, aborting) =
+            create_temp_nix_file("{ nix-tests }: { tests = abort \"aborted test file\"; }\n");
+
+        let reports = NixTestRunner::new(&config::Config::default())
+            .unwrap()
+            .run_batch(vec![passing.clone(), aborting.clone()])
+            .await;
+
+        let_assert!(
+            [
+                TestFileReport::Completed(passed),
+                TestFileReport::Errored(errored)
+            ] = &reports[..]
+        );
+        check!(passed.file == passing);
+        check!(passed.tests.len() == 1);
+        // Only files evaluated on their own have resource stats
+        check!(passed.stats.is_none());
+        check!(errored.file == aborting);
+        check!(errored.error.contains("aborted test file"));
+    }
+
+    #[tokio::test]
+    async fn it_times_out_just_the_hanging_file_of_a_batch() {
+        let (_passing, passing) = create_temp_nix_file(
+            r#"{ nix-tests }:
+nix-tests.runTests {
+  "batched" = helpers: {
+    "passes" = helpers.isTrue true;
+  };
+}
+"#,
+        );
+        let (_hanging, hanging) = create_temp_nix_file(
+            r#"{ nix-tests }:
+{
+  tests = builtins.foldl' (
+    acc: x: acc + builtins.foldl' builtins.add x (builtins.genList (y: y) 100000)
+  ) 0 (builtins.genList (x: x) 100000);
+}
+"#,
+        );
+
+        let start = Instant::now();
+        let reports = NixTestRunner::new(&config::Config {
+            timeout: 2000,
+            ..Default::default()
+        })
+        .unwrap()
+        .run_batch(vec![passing.clone(), hanging.clone()])
+        .await;
+
+        check!(start.elapsed() < Duration::from_millis(6000));
+        let_assert!(
+            [
+                TestFileReport::Completed(passed),
+                TestFileReport::TimedOut(timed_out)
+            ] = &reports[..]
+        );
+        check!(passed.file == passing);
+        check!(passed.elapsed < 2000);
+        check!(timed_out.file == hanging);
+    }
+
+    #[tokio::test]
+    async fn it_profiles_each_test() {
+        let (_file, path) = create_temp_nix_file(
+            r#"{ nix-tests }:
+nix-tests.runTests {
+  "quick" = helpers: {
+    "passes" = helpers.isTrue true;
+  };
+
+  "group" = {
+    "slow" = helpers: {
+      "sums" = helpers.isEq (builtins.foldl' builtins.add 0 (builtins.genList (x: x) 1000000)) 499999500000;
+    };
+  };
+}
+"#,
+        );
+
+        let report = NixTestRunner::new(&config::Config {
+            profile: true,
+            ..Default::default()
+        })
+        .unwrap()
+        .run(path.clone())
+        .await;
+
+        let_assert!(TestFileReport::Completed(file_report) = report);
+        let_assert!([quick, slow] = &file_report.tests[..]);
+        check!(quick.path == vec!["quick"]);
+        check!(slow.path == vec!["group", "slow"]);
+        let_assert!((Some(quick), Some(slow)) = (quick.elapsed, slow.elapsed));
+        check!(slow > quick);
+    }
+
+    #[tokio::test]
+    async fn it_runs_the_tests_of_a_group() {
+        let (_file, path) = create_temp_nix_file(
+            r#"{ nix-tests }:
+nix-tests.runTests {
+  "group" = {
+    "first" = helpers: { "passes" = helpers.isTrue true; };
+    "second" = helpers: { "passes" = helpers.isTrue true; };
+  };
+  "other" = helpers: { "throws" = throw "never evaluated"; };
+}
+"#,
+        );
+
+        let (sender, mut receiver) = tokio::sync::mpsc::unbounded_channel();
+        let report = NixTestRunner::new(&config::Config::default())
+            .unwrap()
+            .run_streamed(path, &["group".to_string()], sender)
+            .await;
+
+        let_assert!(TestFileReport::Completed(file_report) = report);
+        let paths: Vec<&[String]> = file_report
+            .tests
+            .iter()
+            .map(|test| &test.path[..])
+            .collect();
+        check!(paths == [["group", "first"], ["group", "second"]]);
+
+        let mut streamed = Vec::new();
+        while let Some(test) = receiver.recv().await {
+            streamed.push(test);
+        }
+        check!(streamed == file_report.tests);
+    }
+
+    #[tokio::test]
+    async fn it_streams_the_tests_run_before_an_error() {
+        let (_file, path) = create_temp_nix_file(
+            r#"{ nix-tests }:
+nix-tests.runTests {
+  "first" = helpers: { "passes" = helpers.isTrue true; };
+  "second" = helpers: { "throws" = helpers.isTrue (throw "broken"); };
+}
+"#,
+        );
+
+        let (sender, mut receiver) = tokio::sync::mpsc::unbounded_channel();
+        let report = NixTestRunner::new(&config::Config::default())
+            .unwrap()
+            .run_streamed(path, &[], sender)
+            .await;
+
+        let_assert!(TestFileReport::Errored(errored) = report);
+        check!(errored.error.contains("broken"));
+        let_assert!(Some(first) = receiver.recv().await);
+        check!(first.path == ["first"]);
+        check!(receiver.recv().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn it_lists_tests_without_running_them() {
+        let (_file, path) = create_temp_nix_file(
+            r#"{ nix-tests }:
+nix-tests.runTests {
+  "group" = {
+    "test" = helpers: rec {
+      ctx = 1;
+      "fails" = helpers.isTrue false;
+      "throws" = helpers.isEq (throw "never evaluated") ctx;
+    };
+  };
+}
+"#,
+        );
+
+        // Listing fails if the thrown value is forced
+        let listings = NixTestRunner::new(&config::Config::default())
+            .unwrap()
+            .list_tests(&path)
+            .await
+            .unwrap();
+
+        let_assert!([listing] = &listings[..]);
+        check!(listing.path == vec!["group", "test"]);
+        let names: Vec<&str> = listing
+            .checks
+            .iter()
+            .map(|check| check.name.as_str())
+            .collect();
+        check!(names == ["fails", "throws"]);
+    }
 }
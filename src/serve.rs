@@ -0,0 +1,725 @@
+use std::{collections::BTreeMap, sync::Arc};
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tokio::{
+    io::{AsyncBufRead, AsyncBufReadExt, AsyncReadExt, AsyncWrite, AsyncWriteExt},
+    sync::{mpsc, Mutex},
+    task::JoinSet,
+};
+
+use crate::{
+    files::TestFile,
+    reports::{TestFileListing, TestFileReport, TestReport},
+    runners::NixTestRunner,
+};
+
+const DISCOVER: &str = "nix-tests.discover";
+const RUN: &str = "nix-tests.run";
+const TEST_COMPLETED: &str = "nix-tests/testCompleted";
+
+const PARSE_ERROR: i64 = -32700;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const INTERNAL_ERROR: i64 = -32603;
+
+#[derive(Deserialize, Debug)]
+struct Request {
+    #[serde(default)]
+    id: Option<Value>,
+    method: String,
+    #[serde(default)]
+    params: Value,
+}
+
+#[derive(Deserialize, Debug)]
+struct ExecuteCommandParams {
+    command: String,
+    #[serde(default)]
+    arguments: Vec<Value>,
+}
+
+#[derive(Deserialize, Debug)]
+struct RunArguments {
+    /// A path or a `file://` URI
+    file: String,
+    /// The group or test to run, every test of the file if empty
+    #[serde(default)]
+    path: Vec<String>,
+}
+
+#[derive(Serialize, Debug, PartialEq, Eq, Clone)]
+struct Diagnostic {
+    range: Range,
+    severity: u8,
+    source: &'static str,
+    message: String,
+}
+
+#[derive(Serialize, Debug, PartialEq, Eq, Clone)]
+struct Range {
+    start: Position,
+    end: Position,
+}
+
+#[derive(Serialize, Debug, PartialEq, Eq, Clone)]
+struct Position {
+    line: u32,
+    character: u32,
+}
+
+impl Diagnostic {
+    /// An error covering a line, numbered from 1 as in report locations.
+    fn error(line: u32, message: String) -> Self {
+        let line = line.saturating_sub(1);
+        Self {
+            range: Range {
+                start: Position { line, character: 0 },
+                end: Position {
+                    line: line + 1,
+                    character: 0,
+                },
+            },
+            severity: 1,
+            source: "nix-tests",
+            message,
+        }
+    }
+}
+
+/// Diagnostics of a test file by the path of the test they belong to, with
+/// problems of the whole file under the empty path.
+type FileDiagnostics = BTreeMap<Vec<String>, Vec<Diagnostic>>;
+
+struct Server {
+    runner: NixTestRunner,
+    diagnostics: Mutex<BTreeMap<String, FileDiagnostics>>,
+    /// Runs of the same file take turns, so the diagnostics of a later run
+    /// can't be overwritten by those of an earlier one finishing after it
+    runs: Mutex<BTreeMap<String, Arc<Mutex<()>>>>,
+    outgoing: mpsc::UnboundedSender<Value>,
+}
+
+/// Serves test discovery and runs to an editor over JSON-RPC, framed as in
+/// the Language Server Protocol. Results of runs are also published as
+/// diagnostics at the failing checks, until `exit` is sent or the input ends.
+pub async fn serve(
+    runner: NixTestRunner,
+    mut reader: impl AsyncBufRead + Unpin,
+    mut writer: impl AsyncWrite + Unpin + Send + 'static,
+) -> anyhow::Result<()> {
+    let (outgoing, mut messages) = mpsc::unbounded_channel::<Value>();
+    let writing = tokio::spawn(async move {
+        while let Some(message) = messages.recv().await {
+            writer.write_all(&frame(&message)).await?;
+            writer.flush().await?;
+        }
+        anyhow::Ok(())
+    });
+
+    let server = Arc::new(Server {
+        runner,
+        diagnostics: Mutex::new(BTreeMap::new()),
+        runs: Mutex::new(BTreeMap::new()),
+        outgoing,
+    });
+    let mut commands = JoinSet::new();
+
+    let mut exited = false;
+    while let Some(content) = read_message(&mut reader).await? {
+        let request = match serde_json::from_slice::<Request>(&content) {
+            Ok(request) => request,
+            Err(err) => {
+                server.send(error_response(Value::Null, PARSE_ERROR, err.to_string()));
+                continue;
+            }
+        };
+
+        match request.method.as_str() {
+            "exit" => {
+                exited = true;
+                break;
+            }
+            // Commands can take a while, so other requests are answered meanwhile
+            "workspace/executeCommand" => {
+                let server = server.clone();
+                commands.spawn(async move { server.execute_command(request).await });
+            }
+            _ => server.handle(request),
+        }
+    }
+
+    // Commands still running are answered when the input just ends
+    if exited {
+        commands.abort_all();
+    }
+    while commands.join_next().await.is_some() {}
+    drop(server);
+
+    writing.await?
+}
+
+impl Server {
+    fn send(&self, message: Value) {
+        // The writer only stops once every message has been sent
+        let _ = self.outgoing.send(message);
+    }
+
+    fn handle(&self, request: Request) {
+        // Notifications other than `exit` need no handling
+        let Some(id) = request.id else {
+            return;
+        };
+
+        let response = match request.method.as_str() {
+            "initialize" => response(
+                id,
+                json!({
+                    "capabilities": {
+                        "executeCommandProvider": { "commands": [DISCOVER, RUN] },
+                    },
+                    "serverInfo": {
+                        "name": "nix-tests",
+                        "version": env!("CARGO_PKG_VERSION"),
+                    },
+                }),
+            ),
+            "shutdown" => response(id, Value::Null),
+            method => error_response(id, METHOD_NOT_FOUND, format!("Unknown method: {}", method)),
+        };
+        self.send(response);
+    }
+
+    async fn execute_command(&self, request: Request) {
+        let id = request.id.unwrap_or(Value::Null);
+
+        let params = match serde_json::from_value::<ExecuteCommandParams>(request.params) {
+            Ok(params) => params,
+            Err(err) => {
+                return self.send(error_response(id, INVALID_PARAMS, err.to_string()));
+            }
+        };
+
+        let result = match params.command.as_str() {
+            DISCOVER => self.discover(params.arguments).await,
+            RUN => self.run(params.arguments).await,
+            command => Err((INVALID_PARAMS, format!("Unknown command: {}", command))),
+        };
+
+        self.send(match result {
+            Ok(result) => response(id, result),
+            Err((code, message)) => error_response(id, code, message),
+        });
+    }
+
+    /// Lists the tests of the test files in the given paths, or the current
+    /// directory.
+    async fn discover(&self, arguments: Vec<Value>) -> Result<Value, (i64, String)> {
+        let mut paths: Vec<String> = serde_json::from_value(Value::Array(arguments))
+            .map_err(|err| (INVALID_PARAMS, err.to_string()))?;
+        if paths.is_empty() {
+            paths.push(".".to_string());
+        }
+        let paths = paths.iter().map(|path| uri_path(path)).collect();
+
+        let test_files =
+            crate::find_files(paths).map_err(|err| (INTERNAL_ERROR, format!("{:#}", err)))?;
+
+        let mut listings = Vec::new();
+        for test_file in test_files {
+            let TestFile::Valid(file) = test_file else {
+                continue;
+            };
+            let (tests, error) = match self.runner.list_tests(&file).await {
+                Ok(tests) => (Some(tests), None),
+                Err(error) => (None, Some(error)),
+            };
+            listings.push(TestFileListing { file, tests, error });
+        }
+
+        Ok(json!(listings))
+    }
+
+    /// Runs a test file or the tests at a path of it, publishing the results
+    /// of each test as diagnostics and a notification as soon as it's run.
+    /// Returns the report of the file, which when the tests didn't complete
+    /// also has the reports of the tests run before.
+    async fn run(&self, arguments: Vec<Value>) -> Result<Value, (i64, String)> {
+        let arguments: RunArguments = arguments
+            .into_iter()
+            .next()
+            .ok_or_else(|| "Expected the test file to run".to_string())
+            .and_then(|argument| serde_json::from_value(argument).map_err(|err| err.to_string()))
+            .map_err(|message| (INVALID_PARAMS, message))?;
+
+        // Locations in reports are absolute, so they can be matched to the file
+        let file = std::fs::canonicalize(uri_path(&arguments.file))
+            .map_err(|err| (INVALID_PARAMS, format!("{}: {}", arguments.file, err)))?
+            .to_string_lossy()
+            .to_string();
+
+        let run = self
+            .runs
+            .lock()
+            .await
+            .entry(file.clone())
+            .or_default()
+            .clone();
+        let _run = run.lock().await;
+
+        // Tests removed from the file since the last run get no report
+        self.publish(&file, |file_diagnostics| {
+            clear(file_diagnostics, &arguments.path)
+        })
+        .await;
+
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+        let running = self
+            .runner
+            .run_streamed(file.clone(), &arguments.path, sender);
+        let receiving = async {
+            let mut completed = Vec::new();
+            while let Some(test) = receiver.recv().await {
+                self.publish(&file, |file_diagnostics| {
+                    update_test(file_diagnostics, &file, &test)
+                })
+                .await;
+                self.send(json!({
+                    "jsonrpc": "2.0",
+                    "method": TEST_COMPLETED,
+                    "params": { "uri": file_uri(&file), "path": test.path, "report": test },
+                }));
+                completed.push(test);
+            }
+            completed
+        };
+        let (report, completed) = tokio::join!(running, receiving);
+
+        self.publish(&file, |file_diagnostics| {
+            update(file_diagnostics, &arguments.path, &report, &completed)
+        })
+        .await;
+
+        let mut result = json!(report);
+        if !matches!(report, TestFileReport::Completed(_)) {
+            result["tests"] = json!(completed);
+        }
+        Ok(result)
+    }
+
+    /// Changes the diagnostics of a test file and publishes them, holding the
+    /// lock until they are sent so they are published in the order they change.
+    async fn publish(&self, file: &str, change: impl FnOnce(&mut FileDiagnostics)) {
+        let mut diagnostics = self.diagnostics.lock().await;
+        let file_diagnostics = diagnostics.entry(file.to_string()).or_default();
+        change(file_diagnostics);
+
+        let published = file_diagnostics
+            .values()
+            .flatten()
+            .cloned()
+            .collect::<Vec<_>>();
+        self.send(json!({
+            "jsonrpc": "2.0",
+            "method": "textDocument/publishDiagnostics",
+            "params": { "uri": file_uri(file), "diagnostics": published },
+        }));
+    }
+}
+
+/// Removes the diagnostics of the tests at `path` and of the whole file.
+fn clear(file_diagnostics: &mut FileDiagnostics, path: &[String]) {
+    file_diagnostics.retain(|test, _| !test.is_empty() && !test.starts_with(path));
+}
+
+/// Replaces the diagnostics of the tests at `path` with those of a report of
+/// running them, or of the tests that completed before they failed to run.
+/// Problems of the whole file are always replaced, and problems of running
+/// the tests are kept at `path`.
+fn update(
+    file_diagnostics: &mut FileDiagnostics,
+    path: &[String],
+    report: &TestFileReport,
+    completed: &[TestReport],
+) {
+    clear(file_diagnostics, path);
+
+    let error = match report {
+        TestFileReport::Completed(report) => {
+            for test in &report.tests {
+                update_test(file_diagnostics, &report.file, test);
+            }
+            return;
+        }
+        TestFileReport::Errored(report) => report.error.clone(),
+        TestFileReport::TimedOut(report) => format!("Timed out after {}ms", report.timeout),
+        TestFileReport::ResourceExceeded(report) => format!("Exceeded {} limit", report.limit),
+    };
+
+    for test in completed {
+        update_test(file_diagnostics, report.file(), test);
+    }
+    file_diagnostics.insert(path.to_vec(), vec![Diagnostic::error(1, error)]);
+}
+
+/// Replaces the diagnostics of a test with those of its failed checks.
+fn update_test(file_diagnostics: &mut FileDiagnostics, file: &str, test: &TestReport) {
+    let diagnostics = test
+        .checks
+        .iter()
+        .filter(|check| !check.success)
+        .map(|check| {
+            let message = format!(
+                "{} -> {}: {}",
+                test.path.join(" -> "),
+                check.name,
+                check.failure.as_deref().unwrap_or("failed")
+            );
+            // Checks defined in another file are shown at the top
+            match check.location.rsplit_once(':') {
+                Some((location, line)) if location == file => {
+                    Diagnostic::error(line.parse().unwrap_or(1), message)
+                }
+                _ => Diagnostic::error(1, format!("{} at {}", message, check.location)),
+            }
+        })
+        .collect::<Vec<_>>();
+
+    if diagnostics.is_empty() {
+        file_diagnostics.remove(&test.path);
+    } else {
+        file_diagnostics.insert(test.path.clone(), diagnostics);
+    }
+}
+
+/// Reads the content of a message framed by a `Content-Length` header, or
+/// `None` at the end of the input.
+async fn read_message(reader: &mut (impl AsyncBufRead + Unpin)) -> anyhow::Result<Option<Vec<u8>>> {
+    let mut length = None;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).await? == 0 {
+            return Ok(None);
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.eq_ignore_ascii_case("content-length") {
+                length = Some(
+                    value
+                        .trim()
+                        .parse::<usize>()
+                        .context(format!("Invalid Content-Length header: {}", line))?,
+                );
+            }
+        }
+    }
+
+    let length = length.context("Missing Content-Length header")?;
+    let mut content = vec![0; length];
+    reader.read_exact(&mut content).await?;
+    Ok(Some(content))
+}
+
+fn frame(message: &Value) -> Vec<u8> {
+    let content = message.to_string();
+    format!("Content-Length: {}\r\n\r\n{}", content.len(), content).into_bytes()
+}
+
+fn response(id: Value, result: Value) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "result": result })
+}
+
+fn error_response(id: Value, code: i64, message: String) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "error": { "code": code, "message": message } })
+}
+
+fn file_uri(path: &str) -> String {
+    let mut uri = "file://".to_string();
+    for byte in path.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => {
+                uri.push(byte as char)
+            }
+            _ => uri.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    uri
+}
+
+/// The path of a `file://` URI, or the given path when it's not a URI.
+fn uri_path(uri: &str) -> String {
+    let Some(path) = uri.strip_prefix("file://") else {
+        return uri.to_string();
+    };
+
+    let mut bytes = Vec::new();
+    let mut rest = path.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        let decoded = (byte == b'%')
+            .then(|| tail.get(..2))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok());
+        match decoded {
+            Some(decoded) => {
+                bytes.push(decoded);
+                rest = &tail[2..];
+            }
+            None => {
+                bytes.push(byte);
+                rest = tail;
+            }
+        }
+    }
+    String::from_utf8_lossy(&bytes).to_string()
+}
+
+#[cfg(test)]
+mod serve_tests {
+    use assert2::{check, let_assert};
+    use tokio::io::{duplex, BufReader};
+
+    use super::*;
+    use crate::{
+        reports::{CheckReport, TestFileCompletedReport, TestFileErroredReport, TestReport},
+        runners::config::Config,
+    };
+
+    async fn exchange(requests: &[Value]) -> Vec<Value> {
+        let input: Vec<u8> = requests.iter().flat_map(frame).collect();
+        let (writer, output) = duplex(64 * 1024);
+
+        let runner = NixTestRunner::new(&Config::default()).unwrap();
+        serve(runner, BufReader::new(&input[..]), writer)
+            .await
+            .unwrap();
+
+        let mut output = BufReader::new(output);
+        let mut messages = Vec::new();
+        while let Some(content) = read_message(&mut output).await.unwrap() {
+            messages.push(serde_json::from_slice(&content).unwrap());
+        }
+        messages
+    }
+
+    fn completed(file: &str, tests: Vec<TestReport>) -> TestFileReport {
+        TestFileReport::Completed(TestFileCompletedReport {
+            file: file.to_string(),
+            tests,
+            elapsed: 10,
+            mocks: vec![],
+            cached: false,
+            stats: None,
+        })
+    }
+
+    fn test(path: &[&str], checks: Vec<CheckReport>) -> TestReport {
+        TestReport {
+            success: checks.iter().all(|check| check.success),
+            path: path.iter().map(|name| name.to_string()).collect(),
+            case: None,
+            location: "/tests/a.nix:2".to_string(),
+            checks,
+            elapsed: None,
+        }
+    }
+
+    fn failed_check(name: &str, location: &str) -> CheckReport {
+        CheckReport {
+            name: name.to_string(),
+            success: false,
+            failure: Some("Expected: 1\nGot: 2".to_string()),
+            location: location.to_string(),
+            property: None,
+            build: None,
+            definitions: vec![],
+        }
+    }
+
+    #[tokio::test]
+    async fn it_answers_requests_until_exit() {
+        let messages = exchange(&[
+            json!({"jsonrpc": "2.0", "id": 1, "method": "initialize", "params": {}}),
+            json!({"jsonrpc": "2.0", "method": "initialized", "params": {}}),
+            json!({"jsonrpc": "2.0", "id": 2, "method": "textDocument/hover"}),
+            json!({"jsonrpc": "2.0", "id": 3, "method": "shutdown"}),
+            json!({"jsonrpc": "2.0", "method": "exit"}),
+            json!({"jsonrpc": "2.0", "id": 4, "method": "shutdown"}),
+        ])
+        .await;
+
+        let_assert!([initialize, unknown, shutdown] = &messages[..]);
+        check!(
+            initialize["result"]["capabilities"]["executeCommandProvider"]["commands"]
+                == json!([DISCOVER, RUN])
+        );
+        check!(unknown["id"] == 2);
+        check!(unknown["error"]["code"] == METHOD_NOT_FOUND);
+        check!(*shutdown == json!({"jsonrpc": "2.0", "id": 3, "result": null}));
+    }
+
+    #[tokio::test]
+    async fn it_rejects_invalid_commands() {
+        let messages = exchange(&[
+            json!({"jsonrpc": "2.0", "id": 1, "method": "workspace/executeCommand", "params": {"command": "unknown"}}),
+        ])
+        .await;
+
+        let_assert!([unknown] = &messages[..]);
+        check!(unknown["error"]["code"] == INVALID_PARAMS);
+        check!(unknown["error"]["message"] == "Unknown command: unknown");
+    }
+
+    #[test]
+    fn it_turns_failed_checks_into_diagnostics() {
+        let mut diagnostics = FileDiagnostics::new();
+        let report = completed(
+            "/tests/a.nix",
+            vec![
+                test(
+                    &["group", "test"],
+                    vec![failed_check("is one", "/tests/a.nix:4")],
+                ),
+                test(
+                    &["other"],
+                    vec![failed_check("imported", "/lib/checks.nix:7")],
+                ),
+            ],
+        );
+
+        update(&mut diagnostics, &[], &report, &[]);
+
+        check!(
+            diagnostics[&vec!["group".to_string(), "test".to_string()]]
+                == [Diagnostic::error(
+                    4,
+                    "group -> test -> is one: Expected: 1\nGot: 2".to_string()
+                )]
+        );
+        check!(
+            diagnostics[&vec!["group".to_string(), "test".to_string()]][0]
+                .range
+                .start
+                .line
+                == 3
+        );
+        check!(
+            diagnostics[&vec!["other".to_string()]]
+                == [Diagnostic::error(
+                    1,
+                    "other -> imported: Expected: 1\nGot: 2 at /lib/checks.nix:7".to_string()
+                )]
+        );
+    }
+
+    #[test]
+    fn it_keeps_diagnostics_of_tests_not_run() {
+        let mut diagnostics = FileDiagnostics::new();
+        update(
+            &mut diagnostics,
+            &[],
+            &completed(
+                "/tests/a.nix",
+                vec![
+                    test(&["first"], vec![failed_check("check", "/tests/a.nix:4")]),
+                    test(&["second"], vec![failed_check("check", "/tests/a.nix:8")]),
+                ],
+            ),
+            &[],
+        );
+
+        update(
+            &mut diagnostics,
+            &["first".to_string()],
+            &completed("/tests/a.nix", vec![test(&["first"], vec![])]),
+            &[],
+        );
+        check!(diagnostics.keys().collect::<Vec<_>>() == [&vec!["second".to_string()]]);
+
+        update(
+            &mut diagnostics,
+            &[],
+            &TestFileReport::Errored(TestFileErroredReport {
+                file: "/tests/a.nix".to_string(),
+                error: "syntax error".to_string(),
+                elapsed: 1,
+                stats: None,
+            }),
+            &[],
+        );
+        check!(diagnostics[&vec![]] == [Diagnostic::error(1, "syntax error".to_string())]);
+        check!(diagnostics.len() == 1);
+    }
+
+    #[test]
+    fn it_keeps_errors_of_running_a_test_at_its_path() {
+        let mut diagnostics = FileDiagnostics::new();
+        let errored = TestFileReport::Errored(TestFileErroredReport {
+            file: "/tests/a.nix".to_string(),
+            error: "infinite recursion".to_string(),
+            elapsed: 1,
+            stats: None,
+        });
+
+        update(&mut diagnostics, &["first".to_string()], &errored, &[]);
+        update(
+            &mut diagnostics,
+            &["second".to_string()],
+            &completed("/tests/a.nix", vec![test(&["second"], vec![])]),
+            &[],
+        );
+
+        check!(
+            diagnostics[&vec!["first".to_string()]]
+                == [Diagnostic::error(1, "infinite recursion".to_string())]
+        );
+        check!(diagnostics.len() == 1);
+    }
+
+    #[test]
+    fn it_keeps_tests_that_completed_before_an_error() {
+        let mut diagnostics = FileDiagnostics::new();
+        let errored = TestFileReport::Errored(TestFileErroredReport {
+            file: "/tests/a.nix".to_string(),
+            error: "infinite recursion".to_string(),
+            elapsed: 1,
+            stats: None,
+        });
+
+        update(
+            &mut diagnostics,
+            &["group".to_string()],
+            &errored,
+            &[
+                test(
+                    &["group", "first"],
+                    vec![failed_check("check", "/tests/a.nix:4")],
+                ),
+                test(&["group", "second"], vec![]),
+            ],
+        );
+
+        check!(
+            diagnostics[&vec!["group".to_string(), "first".to_string()]]
+                == [Diagnostic::error(
+                    4,
+                    "group -> first -> check: Expected: 1\nGot: 2".to_string()
+                )]
+        );
+        check!(
+            diagnostics[&vec!["group".to_string()]]
+                == [Diagnostic::error(1, "infinite recursion".to_string())]
+        );
+        check!(diagnostics.len() == 2);
+    }
+
+    #[test]
+    fn it_converts_between_paths_and_uris() {
+        check!(file_uri("/tests/my test.nix") == "file:///tests/my%20test.nix");
+        check!(uri_path("file:///tests/my%20test.nix") == "/tests/my test.nix");
+        check!(uri_path("tests/a.nix") == "tests/a.nix");
+    }
+}
@@ -0,0 +1,184 @@
+use std::{cmp::Reverse, str::FromStr};
+
+use anyhow::{bail, Context};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::{files::TestFile, runners::Timings};
+
+/// One of `total` parts of the test files, numbered from 1.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, JsonSchema)]
+pub struct Shard {
+    pub index: usize,
+    pub total: usize,
+}
+
+impl FromStr for Shard {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let (index, total) = value
+            .split_once('/')
+            .context("Expected a shard as INDEX/TOTAL, e.g. 1/4")?;
+        let index: usize = index
+            .parse()
+            .context(format!("Invalid shard index: {}", index))?;
+        let total: usize = total
+            .parse()
+            .context(format!("Invalid shard total: {}", total))?;
+
+        if total == 0 || index == 0 || index > total {
+            bail!("Shard index must be between 1 and {}", total.max(1));
+        }
+
+        Ok(Self { index, total })
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Strategy {
+    /// Assigns each file by a hash of its path, so a file stays in the same
+    /// shard no matter which other files exist.
+    #[default]
+    Hash,
+    /// Balances the time of each shard using timings from previous runs.
+    Timings,
+}
+
+impl Shard {
+    /// Selects the test files belonging to this shard. Every shard must be
+    /// given the same test files, and for [`Strategy::Timings`] the same
+    /// timings, for the shards to cover each file exactly once.
+    pub fn select(
+        &self,
+        test_files: Vec<TestFile>,
+        strategy: Strategy,
+        timings: &Timings,
+    ) -> Vec<TestFile> {
+        let assignments = match strategy {
+            Strategy::Hash => test_files
+                .iter()
+                .map(|test_file| (fnv1a(test_file.name().as_bytes()) % self.total as u64) as usize)
+                .collect(),
+            Strategy::Timings => self.balance(&test_files, timings),
+        };
+
+        test_files
+            .into_iter()
+            .zip(assignments)
+            .filter(|(_, shard)| *shard == self.index - 1)
+            .map(|(test_file, _)| test_file)
+            .collect()
+    }
+
+    /// Assigns files, slowest first, to the shard with the least time so far.
+    /// Files without timings are assumed to take the average time.
+    fn balance(&self, test_files: &[TestFile], timings: &Timings) -> Vec<usize> {
+        let elapsed: Vec<Option<u128>> = test_files
+            .iter()
+            .map(|test_file| timings.get(test_file.name()))
+            .collect();
+        let known: Vec<u128> = elapsed.iter().flatten().copied().collect();
+        let average = if known.is_empty() {
+            1
+        } else {
+            known.iter().sum::<u128>() / known.len() as u128
+        };
+
+        let mut order: Vec<usize> = (0..test_files.len()).collect();
+        order.sort_by_key(|&i| (Reverse(elapsed[i].unwrap_or(average)), test_files[i].name()));
+
+        let mut loads = vec![0u128; self.total];
+        let mut assignments = vec![0; test_files.len()];
+        for i in order {
+            let shard = (0..self.total).min_by_key(|&shard| loads[shard]).unwrap();
+            loads[shard] += elapsed[i].unwrap_or(average);
+            assignments[i] = shard;
+        }
+
+        assignments
+    }
+}
+
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+#[cfg(test)]
+mod shard_tests {
+    use assert2::{check, let_assert};
+
+    use super::*;
+    use crate::reports::{TestFileCompletedReport, TestFileReport};
+
+    fn test_files(names: &[&str]) -> Vec<TestFile> {
+        names
+            .iter()
+            .map(|name| TestFile::Valid(name.to_string()))
+            .collect()
+    }
+
+    fn names(test_files: &[TestFile]) -> Vec<&str> {
+        test_files.iter().map(TestFile::name).collect()
+    }
+
+    #[test]
+    fn it_parses_shards() {
+        check!("2/3".parse::<Shard>().unwrap() == Shard { index: 2, total: 3 });
+
+        for invalid in ["0/3", "4/3", "1/0", "1", "a/3"] {
+            let_assert!(Err(_) = invalid.parse::<Shard>());
+        }
+    }
+
+    #[test]
+    fn it_partitions_test_files_by_hash() {
+        let files = test_files(&["a.nix", "b.nix", "c.nix", "d.nix", "e.nix", "f.nix"]);
+
+        let shards: Vec<Vec<TestFile>> = (1..=3)
+            .map(|index| {
+                Shard { index, total: 3 }.select(files.clone(), Strategy::Hash, &Timings::default())
+            })
+            .collect();
+
+        let mut selected: Vec<&str> = shards.iter().flat_map(|shard| names(shard)).collect();
+        selected.sort();
+        check!(selected == names(&files));
+
+        // A file stays in its shard when other files are added
+        let more = test_files(&[
+            "a.nix", "b.nix", "c.nix", "d.nix", "e.nix", "f.nix", "g.nix",
+        ]);
+        let shard = Shard { index: 1, total: 3 }.select(more, Strategy::Hash, &Timings::default());
+        check!(names(&shard).starts_with(&names(&shards[0])));
+    }
+
+    #[test]
+    fn it_balances_test_files_by_timings() {
+        let mut timings = Timings::default();
+        timings.record(
+            &[("a.nix", 100), ("b.nix", 60), ("c.nix", 50), ("d.nix", 10)].map(
+                |(file, elapsed)| {
+                    TestFileReport::Completed(TestFileCompletedReport {
+                        file: file.to_string(),
+                        tests: vec![],
+                        elapsed,
+                        mocks: vec![],
+                        cached: false,
+                        stats: None,
+                    })
+                },
+            ),
+        );
+        let files = test_files(&["a.nix", "b.nix", "c.nix", "d.nix", "new.nix"]);
+
+        let first = Shard { index: 1, total: 2 }.select(files.clone(), Strategy::Timings, &timings);
+        let second = Shard { index: 2, total: 2 }.select(files, Strategy::Timings, &timings);
+
+        // new.nix is assumed to take the average of 55ms
+        check!(names(&first) == ["a.nix", "c.nix"]);
+        check!(names(&second) == ["b.nix", "d.nix", "new.nix"]);
+    }
+}